@@ -0,0 +1,45 @@
+use std::time::Duration;
+use web_time::Instant;
+
+/// How often, in nodes, the clock is read when there is a time budget. Reading the clock is a
+/// call out to JavaScript under WebAssembly, so it is not done for every node.
+const CLOCK_INTERVAL: usize = 32;
+
+/// Limits how much of a document is searched, so that pathological input cannot stall the
+/// caller.
+pub(crate) struct Budget {
+    nodes_left: Option<usize>,
+    deadline: Option<Instant>,
+    nodes_spent: usize,
+}
+
+impl Budget {
+    pub fn new(nodes: Option<usize>, time: Option<Duration>) -> Self {
+        Budget {
+            nodes_left: nodes,
+            deadline: time.map(|time| Instant::now() + time),
+            nodes_spent: 0,
+        }
+    }
+
+    /// Counts one more node against the budget, returning `false` once the budget is used up.
+    pub fn spend(&mut self) -> bool {
+        if let Some(ref mut nodes_left) = self.nodes_left {
+            if *nodes_left == 0 {
+                return false;
+            }
+            *nodes_left -= 1;
+        }
+        self.nodes_spent += 1;
+        match self.deadline {
+            Some(deadline) if self.nodes_spent % CLOCK_INTERVAL == 0 => Instant::now() < deadline,
+            _ => true,
+        }
+    }
+
+    /// Counts `count` more nodes against the budget, returning `false` if it runs out before
+    /// they are all counted.
+    pub fn spend_nodes(&mut self, count: usize) -> bool {
+        (0..count).all(|_| self.spend())
+    }
+}
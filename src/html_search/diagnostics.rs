@@ -0,0 +1,56 @@
+use super::dom::RcDom;
+use html5ever::interface::QuirksMode as Html5everQuirksMode;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::borrow::Cow;
+#[cfg(feature = "wasm")]
+use tsify::Tsify;
+
+/// Document compatibility mode chosen by the HTML parser.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "kebab-case"))]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub enum QuirksMode {
+    Quirks,
+    LimitedQuirks,
+    #[default]
+    NoQuirks,
+}
+
+impl From<Html5everQuirksMode> for QuirksMode {
+    fn from(value: Html5everQuirksMode) -> Self {
+        match value {
+            Html5everQuirksMode::Quirks => QuirksMode::Quirks,
+            Html5everQuirksMode::LimitedQuirks => QuirksMode::LimitedQuirks,
+            Html5everQuirksMode::NoQuirks => QuirksMode::NoQuirks,
+        }
+    }
+}
+
+/// A single problem html5ever found while parsing the input, with the (1-based) source line the
+/// tokenizer had reached. html5ever does not track columns, so only the line is available.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct ParseError {
+    pub message: Cow<'static, str>,
+    pub line: u64,
+}
+
+/// Parse errors and quirks mode collected while parsing a [`super::Document`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "camelCase"))]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct Diagnostics {
+    pub errors: Vec<ParseError>,
+    pub quirks_mode: QuirksMode,
+}
+
+impl From<&RcDom> for Diagnostics {
+    fn from(value: &RcDom) -> Self {
+        Diagnostics {
+            errors: value.errors.clone(),
+            quirks_mode: value.quirks_mode.into(),
+        }
+    }
+}
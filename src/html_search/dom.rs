@@ -8,6 +8,7 @@
  * except according to those terms.
  */
 
+use super::diagnostics::ParseError;
 use html5ever::{
     interface::tree_builder::{self, ElementFlags, NodeOrText, QuirksMode, TreeSink},
     serialize::{
@@ -73,7 +74,7 @@ impl Drop for Node {
         let mut nodes = mem::take(&mut *self.children.borrow_mut());
         while let Some(node) = nodes.pop() {
             let children = mem::take(&mut *node.children.borrow_mut());
-            nodes.extend(children.into_iter());
+            nodes.extend(children);
             if let NodeData::Element {
                 ref template_contents,
                 ..
@@ -100,7 +101,7 @@ pub type Handle = Rc<Node>;
 
 pub type WeakHandle = Weak<Node>;
 
-fn append(new_parent: &Handle, child: Handle) {
+pub(super) fn append(new_parent: &Handle, child: Handle) {
     let previous_parent = child.parent.replace(Some(Rc::downgrade(new_parent)));
     assert!(previous_parent.is_none());
     new_parent.children.borrow_mut().push(child);
@@ -145,8 +146,38 @@ fn remove_from_parent(target: &Handle) {
 
 pub struct RcDom {
     pub document: Handle,
-    pub errors: Vec<Cow<'static, str>>,
+    pub errors: Vec<ParseError>,
     pub quirks_mode: QuirksMode,
+    is_fragment: bool,
+    current_line: u64,
+    /// Text nodes holding text the parser moved away from where it was written, as it moves
+    /// text misplaced inside a table to before the table. Only kept for the DOM as parsed, not
+    /// for copies of it.
+    pub moved_text: HashSet<*const Node>,
+}
+
+impl RcDom {
+    /// An empty DOM to parse an HTML fragment (rather than a full document) into.
+    pub fn fragment() -> RcDom {
+        RcDom {
+            is_fragment: true,
+            ..RcDom::default()
+        }
+    }
+
+    pub fn is_fragment(&self) -> bool {
+        self.is_fragment
+    }
+
+    /// The node holding the parsed content: the `<html>` element html5ever wraps a fragment in,
+    /// or the document node itself for a full document.
+    pub fn root(&self) -> Handle {
+        if self.is_fragment {
+            self.document.children.borrow()[0].clone()
+        } else {
+            self.document.clone()
+        }
+    }
 }
 
 impl TreeSink for RcDom {
@@ -157,7 +188,14 @@ impl TreeSink for RcDom {
     type Handle = Handle;
 
     fn parse_error(&mut self, msg: Cow<'static, str>) {
-        self.errors.push(msg);
+        self.errors.push(ParseError {
+            message: msg,
+            line: self.current_line,
+        });
+    }
+
+    fn set_current_line(&mut self, line_number: u64) {
+        self.current_line = line_number;
     }
 
     fn get_document(&mut self) -> Handle {
@@ -189,10 +227,10 @@ impl TreeSink for RcDom {
     }
 
     fn elem_name<'a>(&self, target: &'a Handle) -> ExpandedName<'a> {
-        return match target.data {
+        match target.data {
             NodeData::Element { ref name, .. } => name.expanded(),
             _ => panic!("not an element"),
-        };
+        }
     }
 
     fn create_element(
@@ -256,6 +294,7 @@ impl TreeSink for RcDom {
                 let children = parent.children.borrow();
                 let prev = &children[i - 1];
                 if append_to_existing_text(prev, &text) {
+                    self.moved_text.insert(Rc::as_ptr(prev));
                     return;
                 }
                 Node::new(NodeData::Text {
@@ -264,6 +303,10 @@ impl TreeSink for RcDom {
             }
             (NodeOrText::AppendNode(node), _) => node,
         };
+        // text is only put before a sibling when foster parenting moves it out of a table
+        if let NodeData::Text { .. } = child.data {
+            self.moved_text.insert(Rc::as_ptr(&child));
+        }
 
         remove_from_parent(&child);
 
@@ -358,10 +401,105 @@ impl Default for RcDom {
             document: Node::new(NodeData::Document),
             errors: vec![],
             quirks_mode: tree_builder::NoQuirks,
+            is_fragment: false,
+            current_line: 1,
+            moved_text: HashSet::new(),
         }
     }
 }
 
+/// Copies the whole node tree, so a parsed document can be highlighted more than once without
+/// parsing it again.
+impl Clone for RcDom {
+    fn clone(&self) -> RcDom {
+        RcDom {
+            document: deep_copy(&self.document),
+            errors: self.errors.clone(),
+            quirks_mode: self.quirks_mode,
+            is_fragment: self.is_fragment,
+            current_line: self.current_line,
+            moved_text: HashSet::new(),
+        }
+    }
+}
+
+/*
+ * Copies `node` and everything under it, including template contents. Works through the tree
+ * with a stack rather than recursion, like `Node::drop`, so deep trees cannot overflow the call
+ * stack.
+ */
+fn deep_copy(node: &Handle) -> Handle {
+    let root = copy_node(node);
+    let mut pending = vec![(node.clone(), root.clone())];
+    while let Some((original, copy)) = pending.pop() {
+        for child in original.children.borrow().iter() {
+            let child_copy = copy_node(child);
+            append(&copy, child_copy.clone());
+            pending.push((child.clone(), child_copy));
+        }
+        if let (
+            NodeData::Element {
+                template_contents: ref original_contents,
+                ..
+            },
+            NodeData::Element {
+                template_contents: ref copy_contents,
+                ..
+            },
+        ) = (&original.data, &copy.data)
+        {
+            if let Some(ref contents) = *original_contents.borrow() {
+                let contents_copy = copy_node(contents);
+                *copy_contents.borrow_mut() = Some(contents_copy.clone());
+                pending.push((contents.clone(), contents_copy));
+            }
+        }
+    }
+    root
+}
+
+/*
+ * A copy of `node` without its children or template contents.
+ */
+fn copy_node(node: &Handle) -> Handle {
+    Node::new(match node.data {
+        NodeData::Document => NodeData::Document,
+        NodeData::Doctype {
+            ref name,
+            ref public_id,
+            ref system_id,
+        } => NodeData::Doctype {
+            name: name.clone(),
+            public_id: public_id.clone(),
+            system_id: system_id.clone(),
+        },
+        NodeData::Text { ref contents } => NodeData::Text {
+            contents: RefCell::new(contents.borrow().clone()),
+        },
+        NodeData::Comment { ref contents } => NodeData::Comment {
+            contents: contents.clone(),
+        },
+        NodeData::Element {
+            ref name,
+            ref attrs,
+            mathml_annotation_xml_integration_point,
+            ..
+        } => NodeData::Element {
+            name: name.clone(),
+            attrs: RefCell::new(attrs.borrow().clone()),
+            template_contents: RefCell::new(None),
+            mathml_annotation_xml_integration_point,
+        },
+        NodeData::ProcessingInstruction {
+            ref target,
+            ref contents,
+        } => NodeData::ProcessingInstruction {
+            target: target.clone(),
+            contents: contents.clone(),
+        },
+    })
+}
+
 enum SerializeOp {
     Open(Handle),
     Close(QualName),
@@ -0,0 +1,169 @@
+use super::normalization::{normalize, Normalization};
+#[cfg(feature = "transliteration")]
+use super::transliteration::{strip_diacritics, transliterate};
+use std::{collections::HashSet, ops::Range};
+
+/// Text rewritten for matching, remembering which bytes of the original produced each byte, so
+/// matches found in it can be mapped back onto the original.
+pub(crate) struct FoldedText {
+    pub text: String,
+    // for each byte of `text`, the start and end of the original bytes it came from
+    starts: Vec<usize>,
+    ends: Vec<usize>,
+}
+
+impl FoldedText {
+    pub fn with_capacity(capacity: usize) -> Self {
+        FoldedText {
+            text: String::with_capacity(capacity),
+            starts: Vec::with_capacity(capacity),
+            ends: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Appends `output`, produced from bytes `source` of the original.
+    pub fn push(&mut self, output: &str, source: Range<usize>) {
+        self.text.push_str(output);
+        self.starts.resize(self.text.len(), source.start);
+        self.ends.resize(self.text.len(), source.end);
+    }
+
+    /// The bytes of the original which produced bytes `start..end` of the folded text, widened
+    /// to whole original characters (or sequences, such as a kana digraph).
+    pub fn source(&self, start: usize, end: usize) -> Range<usize> {
+        self.starts[start]..self.ends[end - 1]
+    }
+
+    /// Folds this text further with `stage`, keeping the map back onto the original.
+    pub fn then(self, stage: impl FnOnce(&str) -> FoldedText) -> FoldedText {
+        let next = stage(&self.text);
+        let starts = next
+            .starts
+            .iter()
+            .map(|&start| self.starts[start])
+            .collect();
+        let ends = next.ends.iter().map(|&end| self.ends[end - 1]).collect();
+        FoldedText {
+            text: next.text,
+            starts,
+            ends,
+        }
+    }
+}
+
+/// Rewrites applied to both search terms and searched text before matching.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Folding {
+    pub ignored_characters: HashSet<char>,
+    pub normalization: Option<Normalization>,
+    pub collapse_whitespace: bool,
+    #[cfg(feature = "transliteration")]
+    pub transliterate: bool,
+    // lowercase transliterated text fully, not just its ASCII letters, as no term is case-sensitive
+    #[cfg(feature = "transliteration")]
+    pub lowercase: bool,
+}
+
+impl Folding {
+    /// Folds `text`, dropping ignored characters, then normalising, then collapsing whitespace,
+    /// then transliterating (and lowercasing, and spelling Serbian Latin letters without their
+    /// diacritics, when no term is case-sensitive). Returns `None` when there is nothing to
+    /// rewrite.
+    pub fn fold(&self, text: &str) -> Option<FoldedText> {
+        let mut folded = None;
+        if text.contains(|c| self.ignored_characters.contains(&c)) {
+            folded = Some(self.remove_ignored(text));
+        }
+        if let Some(normalization) = self.normalization {
+            folded = Some(fold_with(folded, text, |text| {
+                normalize(text, normalization)
+            }));
+        }
+        if self.collapse_whitespace
+            && has_whitespace_to_collapse(folded.as_ref().map_or(text, |folded| &folded.text))
+        {
+            folded = Some(fold_with(folded, text, collapse_whitespace));
+        }
+        #[cfg(feature = "transliteration")]
+        if self.transliterate {
+            folded = Some(fold_with(folded, text, transliterate));
+            if self.lowercase {
+                folded = Some(fold_with(folded, text, lowercase).then(strip_diacritics));
+            }
+        }
+        folded
+    }
+
+    fn remove_ignored(&self, text: &str) -> FoldedText {
+        let mut folded = FoldedText::with_capacity(text.len());
+        for (start, c) in text.char_indices() {
+            if !self.ignored_characters.contains(&c) {
+                folded.push(c.encode_utf8(&mut [0; 4]), start..start + c.len_utf8());
+            }
+        }
+        folded
+    }
+}
+
+/*
+ * Whether `text` has whitespace other than lone spaces.
+ */
+fn has_whitespace_to_collapse(text: &str) -> bool {
+    let mut previous_whitespace = false;
+    text.chars().any(|c| {
+        let whitespace = c.is_whitespace();
+        let collapse = whitespace && (c != ' ' || previous_whitespace);
+        previous_whitespace = whitespace;
+        collapse
+    })
+}
+
+/*
+ * Replaces each run of whitespace (including no-break spaces and line breaks) with one space.
+ */
+fn collapse_whitespace(text: &str) -> FoldedText {
+    let mut folded = FoldedText::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        if !c.is_whitespace() {
+            folded.push(c.encode_utf8(&mut [0; 4]), start..end);
+            continue;
+        }
+        while let Some((index, next)) = chars.next_if(|&(_, next)| next.is_whitespace()) {
+            end = index + next.len_utf8();
+        }
+        folded.push(" ", start..end);
+    }
+    folded
+}
+
+/*
+ * Lowercases every letter in `text`. The automaton only ignores ASCII case, which misses letters
+ * such as the `Š` transliteration gives.
+ */
+#[cfg(feature = "transliteration")]
+fn lowercase(text: &str) -> FoldedText {
+    let mut folded = FoldedText::with_capacity(text.len());
+    for (start, c) in text.char_indices() {
+        let source = start..start + c.len_utf8();
+        for lower in c.to_lowercase() {
+            folded.push(lower.encode_utf8(&mut [0; 4]), source.clone());
+        }
+    }
+    folded
+}
+
+/*
+ * Applies `stage` to the result of earlier stages, or to `text` if there were none.
+ */
+fn fold_with(
+    folded: Option<FoldedText>,
+    text: &str,
+    stage: impl FnOnce(&str) -> FoldedText,
+) -> FoldedText {
+    match folded {
+        Some(folded) => folded.then(stage),
+        None => stage(text),
+    }
+}
@@ -0,0 +1,171 @@
+use super::{runs::Searchables, Builder, Document, Html, MatchStatistics};
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+};
+
+/// A parsed document highlighted again and again for a query which changes a little at a time,
+/// as in search-as-you-type. The document is highlighted in place, and each search only replaces
+/// the text and attributes whose highlights change. When every term of the next query contains a
+/// term of the last one (as when the user types another letter), only the text and attributes
+/// which matched last time are searched again, since nothing else can match.
+///
+/// Other changes to the query, such as deleting a letter or starting a new term, search the
+/// whole document, as does anything after a search which ran out of budget. Changing any setting
+/// of the builder other than its terms removes every highlight and starts again. Highlights
+/// already in the document are removed once, not for every search.
+///
+/// Serialising the [`Document`] returned still writes the whole page, so on very long pages,
+/// find ranges with [`Builder::ranges_dom`] instead.
+pub struct IncrementalSearch {
+    document: Document,
+    #[cfg(feature = "markdown")]
+    markdown: bool,
+    // fingerprint of the settings, other than the terms, `searchables` was collected with
+    settings: Option<u64>,
+    searchables: Option<Searchables>,
+    // the terms of the last search, unless it ran out of budget
+    previous_terms: Option<Vec<SearchedTerm>>,
+    searched_nodes: usize,
+}
+
+struct SearchedTerm {
+    // the term's text, folded as the searched text is
+    pattern: String,
+    case_sensitive: bool,
+    whole_word: bool,
+}
+
+impl IncrementalSearch {
+    /// Searches `html`, which the searches highlight, for each query.
+    pub fn new(html: Html) -> Self {
+        IncrementalSearch {
+            document: Document {
+                dom: html.0,
+                statistics: MatchStatistics::default(),
+            },
+            #[cfg(feature = "markdown")]
+            markdown: false,
+            settings: None,
+            searchables: None,
+            previous_terms: None,
+            searched_nodes: 0,
+        }
+    }
+
+    /// Searches the Markdown `src`, rendered to HTML once, as [`Builder::search_markdown`] does.
+    #[cfg(feature = "markdown")]
+    pub fn markdown(src: &str) -> Self {
+        IncrementalSearch {
+            markdown: true,
+            ..IncrementalSearch::new(Html::parse_markdown(src))
+        }
+    }
+
+    /// Highlights the search term of `builder` in the document, in place of the last search's
+    /// highlights.
+    pub fn search(&mut self, builder: &Builder) -> &Document {
+        #[cfg(feature = "markdown")]
+        let markdown_builder;
+        #[cfg(feature = "markdown")]
+        let builder = if self.markdown {
+            markdown_builder = builder.markdown_builder();
+            &markdown_builder
+        } else {
+            builder
+        };
+        let dom = &self.document.dom;
+        let settings = settings_fingerprint(builder);
+        if self.settings != Some(settings) {
+            if let Some(ref mut searchables) = self.searchables {
+                searchables.clear();
+            }
+            builder.strip_highlights(&dom.document);
+            self.searchables = Some(builder.searchables(&dom.root()));
+            self.settings = Some(settings);
+            self.previous_terms = None;
+        }
+        let searchables = self
+            .searchables
+            .as_mut()
+            .expect("the searchables are collected along with the settings");
+        let terms: Vec<SearchedTerm> = builder.matcher().map_or_else(Vec::new, |matcher| {
+            matcher
+                .terms()
+                .iter()
+                .zip(matcher.patterns())
+                .map(|(term, pattern)| SearchedTerm {
+                    pattern: pattern.clone(),
+                    case_sensitive: term.case_sensitive,
+                    whole_word: term.whole_word,
+                })
+                .collect()
+        });
+        let narrow = self
+            .previous_terms
+            .as_ref()
+            .is_some_and(|previous_terms| narrows(previous_terms, &terms));
+        let (statistics, searched_nodes) = builder.highlight_searchables(searchables, narrow);
+        self.searched_nodes = searched_nodes;
+        self.previous_terms = (!statistics.budget_exhausted).then_some(terms);
+        self.document.statistics = statistics;
+        &self.document
+    }
+
+    /// How many nodes the last search looked for matches in: every node of the document, or
+    /// when the query was narrowed, only those whose text or attributes matched the search
+    /// before.
+    pub fn searched_nodes(&self) -> usize {
+        self.searched_nodes
+    }
+}
+
+/*
+ * A hash of the settings of `builder`, other than its terms, which decide where matches are.
+ */
+fn settings_fingerprint(builder: &Builder) -> u64 {
+    fn sorted<T: Ord + Copy>(set: &HashSet<T>) -> Vec<T> {
+        let mut items: Vec<T> = set.iter().copied().collect();
+        items.sort_unstable();
+        items
+    }
+
+    let mut hasher = DefaultHasher::new();
+    builder.case_sensitive.hash(&mut hasher);
+    builder.whole_word.hash(&mut hasher);
+    builder.match_kind.hash(&mut hasher);
+    builder.segmentation.hash(&mut hasher);
+    sorted(&builder.folding.ignored_characters).hash(&mut hasher);
+    builder.folding.normalization.hash(&mut hasher);
+    builder.folding.collapse_whitespace.hash(&mut hasher);
+    #[cfg(feature = "transliteration")]
+    builder.folding.transliterate.hash(&mut hasher);
+    sorted(&builder.skip_tags).hash(&mut hasher);
+    builder.marker_attribute.hash(&mut hasher);
+    sorted(&builder.match_attributes).hash(&mut hasher);
+    hasher.finish()
+}
+
+/*
+ * Whether every node matching `terms` also matched `previous_terms`: true when each term
+ * contains a previous term which matches wherever its text appears. A whole-word term can fail to
+ * match inside a longer term, and a case-sensitive one inside a term of any case. An empty
+ * query narrows nothing, though every one of its (no) terms would pass.
+ */
+fn narrows(previous_terms: &[SearchedTerm], terms: &[SearchedTerm]) -> bool {
+    !terms.is_empty()
+        && terms.iter().all(|term| {
+            previous_terms.iter().any(|previous| {
+                if previous.whole_word || (previous.case_sensitive && !term.case_sensitive) {
+                    return false;
+                }
+                if previous.case_sensitive {
+                    term.pattern.contains(&previous.pattern)
+                } else {
+                    term.pattern
+                        .to_ascii_lowercase()
+                        .contains(&previous.pattern.to_ascii_lowercase())
+                }
+            })
+        })
+}
@@ -0,0 +1,66 @@
+use super::{Builder, Document, HighlightRanges, Html};
+use html5ever::tendril::TendrilSink;
+use pulldown_cmark::{html, Options, Parser};
+
+/// Markdown extensions enabled when rendering: tables, footnotes, strikethrough and task lists.
+const MARKDOWN_OPTIONS: Options = Options::ENABLE_TABLES
+    .union(Options::ENABLE_FOOTNOTES)
+    .union(Options::ENABLE_STRIKETHROUGH)
+    .union(Options::ENABLE_TASKLISTS);
+
+impl Html {
+    /// Renders Markdown to HTML and parses it as a fragment, ready for
+    /// [`Builder::search_markdown_dom`].
+    pub fn parse_markdown(src: &str) -> Self {
+        let mut rendered = String::with_capacity(src.len() * 3 / 2);
+        html::push_html(&mut rendered, Parser::new_ext(src, MARKDOWN_OPTIONS));
+        Html(Builder::make_parser().one(rendered))
+    }
+}
+
+impl<'a> Builder<'a> {
+    /// Search inside code blocks in [`Builder::search_markdown`] and
+    /// [`Builder::snippets_markdown`]. Off by default, since code rarely makes a useful
+    /// highlight. Inline code is always searched.
+    pub fn markdown_code_blocks(&mut self, value: bool) -> &mut Self {
+        self.markdown_code_blocks = value;
+        self
+    }
+
+    /// Renders Markdown to HTML and highlights matches of the search term in it. Link
+    /// destinations and image sources become attributes, so are only searched if listed in
+    /// [`Builder::match_attributes`].
+    pub fn search_markdown(&self, src: &str) -> Document {
+        self.search_markdown_dom(Html::parse_markdown(src))
+    }
+
+    /// Like [`Builder::search_markdown`], for Markdown already rendered and parsed with
+    /// [`Html::parse_markdown`].
+    pub fn search_markdown_dom(&self, html: Html) -> Document {
+        self.markdown_builder().search_dom(html)
+    }
+
+    /// Like [`Builder::snippets`], for Markdown.
+    pub fn snippets_markdown(&self, src: &str, context: usize) -> Vec<String> {
+        self.markdown_builder()
+            .snippets_dom(Html::parse_markdown(src), context)
+    }
+
+    /// Like [`Builder::ranges`], for Markdown. Paths lead through the rendered HTML.
+    pub fn ranges_markdown(&self, src: &str) -> HighlightRanges {
+        self.markdown_builder()
+            .ranges_dom(&Html::parse_markdown(src))
+    }
+
+    /*
+     * This builder, skipping the `<pre>` elements code blocks render to unless
+     * `markdown_code_blocks` is set.
+     */
+    pub(super) fn markdown_builder(&self) -> Builder<'a> {
+        let mut builder = self.clone();
+        if !self.markdown_code_blocks {
+            builder.skip_tags.insert("pre");
+        }
+        builder
+    }
+}
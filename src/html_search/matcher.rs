@@ -0,0 +1,370 @@
+use super::folding::Folding;
+use super::segmentation::{is_unspaced, Segmentation};
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+use std::cmp::Reverse;
+#[cfg(feature = "wasm")]
+use tsify::Tsify;
+
+/// How to choose between matches of different terms which overlap, such as `new` and
+/// `new york`, or `apple` and `pineapple`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub enum MatchKind {
+    /// The match which ends first wins (Aho-Corasick standard semantics), so `new` beats
+    /// `new york`.
+    #[default]
+    Standard,
+    /// The match which starts first wins and, of those starting at the same place, the longest.
+    LeftmostLongest,
+    /// The match which starts first wins and, of those starting at the same place, the one for
+    /// the term with the highest [`SearchTerm::priority`] (or given first, for equal priorities).
+    LeftmostFirst,
+    /// Matches are chosen as for `LeftmostLongest`, then matches of other terms lying wholly
+    /// within them are highlighted too, as highlight elements nested inside the outer one:
+    /// `<mark><mark>new</mark> york</mark>`.
+    Nested,
+}
+
+/// A search term, with matching options which override the [`super::Builder`] settings for
+/// this term only. Unlike terms in [`super::Builder::search_term`], `text` may contain spaces.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(deny_unknown_fields, rename_all = "camelCase")
+)]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct SearchTerm {
+    pub text: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub case_sensitive: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub whole_word: Option<bool>,
+    /// Class added to highlights of this term, after any [`super::Builder::highlight_classes`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub class: Option<String>,
+    /// Precedence over overlapping matches of other terms, with [`MatchKind::LeftmostFirst`].
+    /// Higher priorities win; defaults to 0.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: i32,
+}
+
+impl SearchTerm {
+    pub fn new(text: impl Into<String>) -> Self {
+        SearchTerm {
+            text: text.into(),
+            ..SearchTerm::default()
+        }
+    }
+
+    pub fn case_sensitive(mut self, value: bool) -> Self {
+        self.case_sensitive = Some(value);
+        self
+    }
+
+    pub fn whole_word(mut self, value: bool) -> Self {
+        self.whole_word = Some(value);
+        self
+    }
+
+    pub fn class(mut self, value: impl Into<String>) -> Self {
+        self.class = Some(value.into());
+        self
+    }
+
+    pub fn priority(mut self, value: i32) -> Self {
+        self.priority = value;
+        self
+    }
+}
+
+impl From<&str> for SearchTerm {
+    fn from(value: &str) -> Self {
+        SearchTerm::new(value)
+    }
+}
+
+impl From<String> for SearchTerm {
+    fn from(value: String) -> Self {
+        SearchTerm::new(value)
+    }
+}
+
+/// A search term with the builder defaults filled in.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Term<'t> {
+    pub text: &'t str,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub class: Option<&'t str>,
+    pub priority: i32,
+    /// Whether this is a bigram from [`Segmentation::Bigram`], whose overlapping matches are
+    /// merged.
+    pub bigram: bool,
+}
+
+/// A match of `terms[term]` spanning bytes `start..end` of the searched text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub term: usize,
+}
+
+pub(crate) struct Matcher<'t> {
+    automaton: AhoCorasick,
+    terms: Vec<Term<'t>>,
+    // the text of each term, folded as the searched text is
+    patterns: Vec<String>,
+    match_kind: MatchKind,
+    segmentation: Segmentation,
+    folding: Folding,
+}
+
+impl<'t> Matcher<'t> {
+    /// Builds a matcher for `terms`, or returns `None` when there is nothing to search for.
+    pub fn new(
+        terms: Vec<Term<'t>>,
+        match_kind: MatchKind,
+        segmentation: Segmentation,
+        folding: Folding,
+    ) -> Option<Self> {
+        if terms.is_empty() {
+            return None;
+        }
+        // transliterated text has letters, such as `Š`, which are not ASCII but have a lowercase
+        // form, so it is lowercased when every term ignores case
+        #[cfg(feature = "transliteration")]
+        let folding = Folding {
+            lowercase: folding.transliterate && terms.iter().all(|term| !term.case_sensitive),
+            ..folding
+        };
+        let patterns: Vec<String> = terms
+            .iter()
+            .map(|term| {
+                folding
+                    .fold(term.text)
+                    .map_or_else(|| term.text.to_string(), |folded| folded.text)
+            })
+            .collect();
+        let automaton = AhoCorasickBuilder::new()
+            .ascii_case_insensitive(terms.iter().any(|term| !term.case_sensitive))
+            .build(&patterns);
+        Some(Matcher {
+            automaton,
+            terms,
+            patterns,
+            match_kind,
+            segmentation,
+            folding,
+        })
+    }
+
+    pub fn terms(&self) -> &[Term<'t>] {
+        &self.terms
+    }
+
+    /// The text of each term, folded as the searched text is.
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /*
+     * Finds matches in `text`, ordered by start and then longest first. Overlapping matches are
+     * resolved according to the match kind, so matches are disjoint, except with
+     * `MatchKind::Nested`, where a match may lie wholly within an earlier one.
+     */
+    pub fn find(&self, text: &str) -> Vec<Match> {
+        let Some(folded) = self.folding.fold(text) else {
+            return self.find_folded(text);
+        };
+        // several folded matches may map back onto the same original characters, so overlaps
+        // are dropped again
+        let mut matches: Vec<Match> = Vec::new();
+        for found in self.find_folded(&folded.text) {
+            let source = folded.source(found.start, found.end);
+            let search_match = Match {
+                start: source.start,
+                end: source.end,
+                term: found.term,
+            };
+            let overlaps = matches.last().is_some_and(|previous| {
+                search_match.start < previous.end
+                    && (self.match_kind != MatchKind::Nested || search_match.end > previous.end)
+            });
+            if !overlaps {
+                matches.push(search_match);
+            }
+        }
+        matches
+    }
+
+    fn find_folded(&self, text: &str) -> Vec<Match> {
+        let mut candidates: Vec<Match> = self
+            .automaton
+            .find_overlapping_iter(text)
+            .map(|value| Match {
+                start: value.start(),
+                end: value.end(),
+                term: value.pattern(),
+            })
+            // a term made up only of ignored characters folds to nothing, and matches nothing
+            .filter(|candidate| candidate.start < candidate.end && self.accepts(text, candidate))
+            .collect();
+        if self.terms.iter().any(|term| term.bigram) {
+            candidates = self.merge_bigrams(candidates);
+        }
+        match self.match_kind {
+            // the match which ends first (and then the longest) is reported first
+            MatchKind::Standard => {
+                candidates.sort_by_key(|candidate| (candidate.end, candidate.start));
+            }
+            MatchKind::LeftmostLongest | MatchKind::Nested => {
+                candidates.sort_by_key(|candidate| (candidate.start, Reverse(candidate.end)));
+            }
+            MatchKind::LeftmostFirst => candidates.sort_by_key(|candidate| {
+                (
+                    candidate.start,
+                    Reverse(self.terms[candidate.term].priority),
+                    candidate.term,
+                )
+            }),
+        }
+        if self.match_kind == MatchKind::Nested {
+            return nest(candidates);
+        }
+        let mut matches: Vec<Match> = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            if matches
+                .last()
+                .is_none_or(|previous| candidate.start >= previous.end)
+            {
+                matches.push(candidate);
+            }
+        }
+        matches
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        !self.find(text).is_empty()
+    }
+
+    /*
+     * Checks the per-term options the automaton cannot: the automaton ignores ASCII case if any
+     * term does, so case-sensitive terms are compared again here.
+     */
+    fn accepts(&self, text: &str, candidate: &Match) -> bool {
+        let term = &self.terms[candidate.term];
+        let matched = &text[candidate.start..candidate.end];
+        (!term.case_sensitive || matched == self.patterns[candidate.term])
+            && (!term.whole_word
+                || is_whole_word(
+                    text,
+                    candidate.start,
+                    candidate.end,
+                    self.segmentation == Segmentation::Bigram,
+                ))
+    }
+
+    /*
+     * Replaces bigram matches which overlap each other with one match spanning them all,
+     * attributed to the term of the first.
+     */
+    fn merge_bigrams(&self, candidates: Vec<Match>) -> Vec<Match> {
+        let (mut bigrams, mut merged): (Vec<Match>, Vec<Match>) = candidates
+            .into_iter()
+            .partition(|candidate| self.terms[candidate.term].bigram);
+        bigrams.sort_by_key(|bigram| (bigram.start, bigram.end));
+        let mut current: Option<Match> = None;
+        for bigram in bigrams {
+            match current {
+                Some(ref mut span) if bigram.start < span.end => {
+                    span.end = span.end.max(bigram.end);
+                }
+                _ => merged.extend(current.replace(bigram)),
+            }
+        }
+        merged.extend(current);
+        merged
+    }
+}
+
+/*
+ * Keeps each of `candidates` (sorted by start, then longest first) which either lies wholly
+ * within the innermost match kept so far, or starts after it ends. Identical spans are kept
+ * once, and partial overlaps are dropped.
+ */
+fn nest(candidates: Vec<Match>) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::with_capacity(candidates.len());
+    // ends of the kept matches enclosing the current position, innermost last
+    let mut enclosing: Vec<usize> = Vec::new();
+    for candidate in candidates {
+        while enclosing.last().is_some_and(|&end| end <= candidate.start) {
+            enclosing.pop();
+        }
+        let is_duplicate = matches.last().is_some_and(|previous| {
+            previous.start == candidate.start && previous.end == candidate.end
+        });
+        if !is_duplicate && enclosing.last().is_none_or(|&end| candidate.end <= end) {
+            enclosing.push(candidate.end);
+            matches.push(candidate);
+        }
+    }
+    matches
+}
+
+/// Whether `c` is punctuation to trim from the ends of search terms: ASCII punctuation, plus
+/// common quotation marks, dashes and CJK punctuation.
+pub(crate) fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || matches!(
+            c,
+            '‘' | '’'
+                | '‚'
+                | '“'
+                | '”'
+                | '„'
+                | '«'
+                | '»'
+                | '‹'
+                | '›'
+                | '–'
+                | '—'
+                | '…'
+                | '¡'
+                | '¿'
+                | '、'
+                | '。'
+                | '，'
+                | '！'
+                | '？'
+                | '「'
+                | '」'
+        )
+}
+
+/*
+ * Whether the match from `start` to `end` in `text` is neither preceded nor followed by a letter or
+ * digit. With `segmented`, characters of unspaced scripts are words on their own, so there is
+ * always a word boundary next to them.
+ */
+fn is_whole_word(text: &str, start: usize, end: usize, segmented: bool) -> bool {
+    let is_boundary = |inside: Option<char>, outside: Option<char>| {
+        !outside.is_some_and(char::is_alphanumeric)
+            || segmented && (inside.is_some_and(is_unspaced) || outside.is_some_and(is_unspaced))
+    };
+    is_boundary(
+        text[start..end].chars().next(),
+        text[..start].chars().next_back(),
+    ) && is_boundary(
+        text[start..end].chars().next_back(),
+        text[end..].chars().next(),
+    )
+}
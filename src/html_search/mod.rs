@@ -22,31 +22,144 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+mod budget;
+mod diagnostics;
 mod dom;
+mod folding;
+mod incremental;
+#[cfg(feature = "markdown")]
+mod markdown;
+mod matcher;
+mod normalization;
+mod position;
+mod ranges;
+mod runs;
+mod segmentation;
+mod serialization;
+mod snippets;
+mod source;
+mod splice;
+mod statistics;
+#[cfg(feature = "transliteration")]
+mod transliteration;
 
 #[cfg(test)]
 mod tests;
 
-use aho_corasick::AhoCorasickBuilder;
+pub use diagnostics::{Diagnostics, ParseError, QuirksMode};
 use dom::{Handle, Node, NodeData, RcDom, SerializableHandle};
+use folding::Folding;
 use html5ever::{
     driver,
     interface::tree_builder::{NodeOrText, TreeSink},
     local_name, namespace_url, ns,
-    serialize::{serialize, SerializeOpts},
+    serialize::{Serialize, TraversalScope},
     tendril::*,
     Attribute, QualName,
 };
+pub use incremental::IncrementalSearch;
+use matcher::{is_punctuation, Matcher, Term};
+pub use matcher::{MatchKind, SearchTerm};
+pub use normalization::Normalization;
+pub use position::Offset;
+pub use ranges::{HighlightRange, HighlightRanges};
+use segmentation::segments;
+pub use segmentation::Segmentation;
+use serialization::MarkupSerializer;
+pub use serialization::{EntityStyle, MarkupFormat, SerializationOptions, SerializationScope};
+pub use source::{SourcePosition, SourceSpan};
+pub use splice::SplicedHtml;
+pub use statistics::{MatchStatistics, TermStatistics};
 use std::{
     cell::RefCell,
+    collections::{HashSet, VecDeque},
     fmt::{self, Display},
     mem,
     rc::Rc,
+    time::Duration,
 };
 
-#[derive(Debug, Default)]
+const FIRST_MATCH_ID: &str = "search-match";
+const MARKER_ATTRIBUTE: &str = "data-searchlite";
+const ATTRIBUTE_MATCH_ATTRIBUTE: &str = "data-searchlite-attr-match";
+
+const HIGHLIGHT_TAG: &str = "mark";
+
+/// Elements whose content is not searched by default: highlighting inside these would either
+/// not render or change what the page does.
+pub const SKIP_TAGS: [&str; 6] = ["head", "script", "style", "template", "textarea", "title"];
+
+/// Characters skipped over when matching by default: invisible characters inserted for
+/// typographic reasons, which would otherwise split words. These are the soft hyphen (`&shy;`),
+/// zero-width space, zero-width non-joiner, zero-width joiner, word joiner and zero-width
+/// no-break space.
+pub const IGNORED_CHARACTERS: [char; 6] = [
+    '\u{AD}', '\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}',
+];
+
+/// Configures and runs a search over an HTML fragment.
+///
+/// Setters take `&mut self` and return `&mut Self`, so they can be chained, ending with
+/// [`Builder::search`] (or [`Builder::search_dom`] for an already-parsed fragment).
+#[derive(Clone, Debug)]
 pub struct Builder<'a> {
     search_term: Option<&'a str>,
+    search_terms: Vec<SearchTerm>,
+    case_sensitive: bool,
+    whole_word: bool,
+    match_kind: MatchKind,
+    segmentation: Segmentation,
+    folding: Folding,
+    highlight_tag: &'a str,
+    highlight_classes: Vec<&'a str>,
+    skip_tags: HashSet<&'a str>,
+    marker_attribute: (&'a str, &'a str),
+    match_attributes: HashSet<&'a str>,
+    attribute_match_class: Option<&'a str>,
+    max_highlights: Option<usize>,
+    max_highlights_per_term: Option<usize>,
+    node_budget: Option<usize>,
+    time_budget: Option<Duration>,
+    min_term_length: usize,
+    trim_punctuation: bool,
+    deduplicate_terms: bool,
+    max_terms: Option<usize>,
+    #[cfg(feature = "markdown")]
+    markdown_code_blocks: bool,
+}
+
+impl<'a> Default for Builder<'a> {
+    fn default() -> Self {
+        Builder {
+            search_term: None,
+            search_terms: Vec::new(),
+            case_sensitive: false,
+            whole_word: false,
+            match_kind: MatchKind::Standard,
+            segmentation: Segmentation::Whitespace,
+            folding: Folding {
+                ignored_characters: IGNORED_CHARACTERS.into_iter().collect(),
+                collapse_whitespace: true,
+                ..Folding::default()
+            },
+            highlight_tag: HIGHLIGHT_TAG,
+            highlight_classes: Vec::new(),
+            skip_tags: SKIP_TAGS.into_iter().collect(),
+            marker_attribute: (MARKER_ATTRIBUTE, ""),
+            match_attributes: HashSet::new(),
+            attribute_match_class: None,
+            max_highlights: None,
+            max_highlights_per_term: None,
+            node_budget: None,
+            time_budget: None,
+            min_term_length: 1,
+            trim_punctuation: false,
+            deduplicate_terms: false,
+            max_terms: None,
+            #[cfg(feature = "markdown")]
+            markdown_code_blocks: false,
+        }
+    }
 }
 
 impl<'a> Builder<'a> {
@@ -54,180 +167,583 @@ impl<'a> Builder<'a> {
         Self::default()
     }
 
+    /// Terms to highlight, separated by whitespace. Nothing is highlighted when this is `None`
+    /// and there are no [`Builder::search_terms`].
     pub fn search_term(&mut self, value: Option<&'a str>) -> &mut Self {
         self.search_term = value;
         self
     }
 
-    fn search_child(&self, _child: &mut Handle) -> bool {
-        true
+    /// Terms to highlight, given individually, so they may contain spaces and have their own
+    /// matching options. These are searched for as well as any [`Builder::search_term`] terms.
+    pub fn search_terms(&mut self, value: Vec<SearchTerm>) -> &mut Self {
+        self.search_terms = value;
+        self
     }
 
-    pub fn search_dom(&self, mut dom: RcDom) -> Document {
-        let mut stack = Vec::new();
-        let mut removed = Vec::new();
+    /// Match the case of search terms exactly. By default, matching ignores ASCII case.
+    pub fn case_sensitive(&mut self, value: bool) -> &mut Self {
+        self.case_sensitive = value;
+        self
+    }
 
-        let body = {
-            let children = dom.document.children.borrow();
-            children[0].clone()
-        };
-        stack.extend(
-            mem::take(&mut *body.children.borrow_mut())
-                .into_iter()
-                .rev(),
-        );
-        let mut already_matched = false;
-
-        while let Some(mut node) = stack.pop() {
-            let parent = node.parent.replace(None).expect("a node in the DOM will have a parent, except the root, which is not searched")
-                .upgrade().expect("a node's parent will be pointed to by its parent (or the root pointer), and will not be dropped");
-            let pass_search = self.search_child(&mut node);
-            if pass_search {
-                if self.search_term.is_some() {
-                    if let Some(value) =
-                        self.replacement_node(&mut node, &mut dom, &mut already_matched)
+    /// Only highlight matches which are whole words, so `apple` no longer matches inside
+    /// `Snapple`.
+    pub fn whole_word(&mut self, value: bool) -> &mut Self {
+        self.whole_word = value;
+        self
+    }
+
+    /// How overlapping matches of different terms are resolved. See [`MatchKind`].
+    pub fn match_kind(&mut self, value: MatchKind) -> &mut Self {
+        self.match_kind = value;
+        self
+    }
+
+    /// How query terms in scripts written without spaces, such as Chinese, Japanese and Thai,
+    /// are broken up. Defaults to [`Segmentation::Whitespace`].
+    pub fn segmentation(&mut self, value: Segmentation) -> &mut Self {
+        self.segmentation = value;
+        self
+    }
+
+    /// Characters skipped over when matching, replacing the defaults in
+    /// [`IGNORED_CHARACTERS`], so `search&shy;able` matches `searchable`. Highlights of a match
+    /// containing them still cover them. Pass an empty set to match every character.
+    pub fn ignored_characters(&mut self, value: HashSet<char>) -> &mut Self {
+        self.folding.ignored_characters = value;
+        self
+    }
+
+    /// Treat any run of whitespace, including line breaks and no-break spaces, as a single space
+    /// when matching, so the term `new york` matches `new&nbsp;york` and `new` and `york` on
+    /// separate lines. On by default. A phrase matches across text split by comments, with a
+    /// highlight element either side of each comment, but not across elements such as `<b>` or
+    /// `<br>`.
+    pub fn collapse_whitespace(&mut self, value: bool) -> &mut Self {
+        self.folding.collapse_whitespace = value;
+        self
+    }
+
+    /// Unicode normalisation applied to terms and searched text before matching, so text
+    /// written with combining characters (or, with [`Normalization::Nfkc`], full-width or other
+    /// compatibility characters) matches. Highlights cover the original characters. Defaults to
+    /// `None`, matching text as it is encoded.
+    pub fn normalization(&mut self, value: Option<Normalization>) -> &mut Self {
+        self.folding.normalization = value;
+        self
+    }
+
+    /// Match across scripts by transliterating both the terms and the searched text into Latin
+    /// script: Serbian Cyrillic into Gaj's Latin alphabet, and hiragana and katakana into
+    /// Hepburn romaji (as typed, so `とうきょう` is `toukyou`). Highlights cover the original
+    /// characters. Unless a term is case-sensitive, case is ignored for all letters, not just
+    /// ASCII ones, and Serbian Latin letters match without their diacritics (`sabac` matches
+    /// `Šabac`).
+    #[cfg(feature = "transliteration")]
+    pub fn transliterate(&mut self, value: bool) -> &mut Self {
+        self.folding.transliterate = value;
+        self
+    }
+
+    /// Name of the element wrapped around matches. Defaults to `mark`.
+    pub fn highlight_tag(&mut self, value: &'a str) -> &mut Self {
+        self.highlight_tag = value;
+        self
+    }
+
+    /// Classes added to every highlight element. Terms may add a class of their own (see
+    /// [`SearchTerm::class`]).
+    pub fn highlight_classes(&mut self, value: Vec<&'a str>) -> &mut Self {
+        self.highlight_classes = value;
+        self
+    }
+
+    /// Elements whose content is not searched, replacing the defaults in [`SKIP_TAGS`].
+    pub fn skip_tags(&mut self, value: HashSet<&'a str>) -> &mut Self {
+        self.skip_tags = value;
+        self
+    }
+
+    /// Attribute (name and value) added to every highlight element searchlite inserts, so that
+    /// highlights can later be told apart from elements the author wrote. Only elements carrying
+    /// it are removed as highlights. Defaults to `data-searchlite=""`.
+    pub fn marker_attribute(&mut self, value: (&'a str, &'a str)) -> &mut Self {
+        self.marker_attribute = value;
+        self
+    }
+
+    /// Attributes (e.g. `alt`, `title` or `aria-label`) whose values are also searched. Text in
+    /// an attribute cannot be wrapped in a `<mark>`, so instead the owning element gets a
+    /// `data-searchlite-attr-match` attribute listing the attributes which matched, for example
+    /// `<img alt="apple" data-searchlite-attr-match="alt">`.
+    pub fn match_attributes(&mut self, value: HashSet<&'a str>) -> &mut Self {
+        self.match_attributes = value;
+        self
+    }
+
+    /// Class added to elements with a matching attribute (see [`Builder::match_attributes`]),
+    /// in addition to the `data-searchlite-attr-match` attribute.
+    pub fn attribute_match_class(&mut self, value: Option<&'a str>) -> &mut Self {
+        self.attribute_match_class = value;
+        self
+    }
+
+    /// Most matches to highlight in a document, across all terms. Matches past the limit are
+    /// left as plain text but still counted in [`Document::statistics`].
+    pub fn max_highlights(&mut self, value: Option<usize>) -> &mut Self {
+        self.max_highlights = value;
+        self
+    }
+
+    /// Most matches to highlight for any one term. Matches past the limit are left as plain
+    /// text but still counted in [`Document::statistics`].
+    pub fn max_highlights_per_term(&mut self, value: Option<usize>) -> &mut Self {
+        self.max_highlights_per_term = value;
+        self
+    }
+
+    /// Most nodes to visit while searching. Once it is used up the rest of the document is left
+    /// as it is and [`MatchStatistics::budget_exhausted`] is set.
+    pub fn node_budget(&mut self, value: Option<usize>) -> &mut Self {
+        self.node_budget = value;
+        self
+    }
+
+    /// Longest to spend searching. Once it is used up the rest of the document is left as it is
+    /// and [`MatchStatistics::budget_exhausted`] is set. The clock is only checked every few
+    /// nodes, so searching may run slightly over.
+    pub fn time_budget(&mut self, value: Option<Duration>) -> &mut Self {
+        self.time_budget = value;
+        self
+    }
+
+    /// Terms shorter than this many characters (after [`Builder::trim_punctuation`]) are
+    /// dropped from the query. Defaults to 1, so only empty terms are dropped.
+    pub fn min_term_length(&mut self, value: usize) -> &mut Self {
+        self.min_term_length = value;
+        self
+    }
+
+    /// Strip punctuation from the start and end of each term, so `"apple,"` searches for
+    /// `apple` and a term of only punctuation, such as `"--"`, is dropped.
+    pub fn trim_punctuation(&mut self, value: bool) -> &mut Self {
+        self.trim_punctuation = value;
+        self
+    }
+
+    /// Drop terms which repeat an earlier term. Case-insensitive terms repeat each other when
+    /// they differ only in ASCII case.
+    pub fn deduplicate_terms(&mut self, value: bool) -> &mut Self {
+        self.deduplicate_terms = value;
+        self
+    }
+
+    /// Most terms to search for; any after the first `value` (once the other rules above have
+    /// dropped terms) are ignored.
+    pub fn max_terms(&mut self, value: Option<usize>) -> &mut Self {
+        self.max_terms = value;
+        self
+    }
+
+    /// The terms actually searched for, after [`Builder::min_term_length`],
+    /// [`Builder::trim_punctuation`], [`Builder::deduplicate_terms`] and [`Builder::max_terms`]
+    /// have been applied, in the order they appear in [`MatchStatistics::terms`].
+    pub fn effective_terms(&self) -> Vec<String> {
+        self.terms()
+            .into_iter()
+            .map(|term| term.text.to_string())
+            .collect()
+    }
+
+    fn search_children(&self, node: &Handle) -> bool {
+        match node.data {
+            NodeData::Element { ref name, .. } => !self.skip_tags.contains(&*name.local),
+            _ => true,
+        }
+    }
+
+    fn terms(&self) -> Vec<Term<'_>> {
+        let query_terms = self
+            .search_term
+            .into_iter()
+            .flat_map(str::split_whitespace)
+            .flat_map(|text| match self.segmentation {
+                Segmentation::Whitespace => vec![(text, false)],
+                Segmentation::Bigram => segments(text)
+                    .into_iter()
+                    .map(|segment| (segment.text, segment.bigram))
+                    .collect(),
+            })
+            .map(|(text, bigram)| Term {
+                text,
+                case_sensitive: self.case_sensitive,
+                whole_word: self.whole_word,
+                class: None,
+                priority: 0,
+                bigram,
+            });
+        let search_terms = self.search_terms.iter().map(|term| Term {
+            text: &term.text,
+            case_sensitive: term.case_sensitive.unwrap_or(self.case_sensitive),
+            whole_word: term.whole_word.unwrap_or(self.whole_word),
+            class: term.class.as_deref(),
+            priority: term.priority,
+            bigram: false,
+        });
+        let mut seen = HashSet::new();
+        query_terms
+            .chain(search_terms)
+            .map(|term| Term {
+                text: if self.trim_punctuation {
+                    term.text.trim_matches(is_punctuation)
+                } else {
+                    term.text
+                },
+                ..term
+            })
+            .filter(|term| term.text.chars().count() >= self.min_term_length.max(1))
+            .filter(|term| {
+                !self.deduplicate_terms
+                    || seen.insert(if term.case_sensitive {
+                        (term.text.to_string(), true)
+                    } else {
+                        (term.text.to_ascii_lowercase(), false)
+                    })
+            })
+            .take(self.max_terms.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    fn matcher(&self) -> Option<Matcher<'_>> {
+        Matcher::new(
+            self.terms(),
+            self.match_kind,
+            self.segmentation,
+            self.folding.clone(),
+        )
+    }
+
+    /*
+     * The value of the `class` attribute on highlights of a term with class `term_class`, if any.
+     */
+    fn highlight_class(&self, term_class: Option<&str>) -> Option<String> {
+        let classes: Vec<&str> = self
+            .highlight_classes
+            .iter()
+            .copied()
+            .chain(term_class)
+            .collect();
+        (!classes.is_empty()).then(|| classes.join(" "))
+    }
+
+    fn is_highlight(&self, node: &Handle) -> bool {
+        let (marker_name, marker_value) = self.marker_attribute;
+        match node.data {
+            NodeData::Element { ref attrs, .. } => attrs
+                .borrow()
+                .iter()
+                .any(|attr| &*attr.name.local == marker_name && &*attr.value == marker_value),
+            _ => false,
+        }
+    }
+
+    /*
+     * Replaces each highlight element below `root` with its children and merges any text nodes
+     * left next to each other, so the tree is as it was before highlighting.
+     */
+    fn strip_highlights(&self, root: &Handle) {
+        let mut stack = vec![root.clone()];
+        while let Some(node) = stack.pop() {
+            let mut queue: VecDeque<Handle> = mem::take(&mut *node.children.borrow_mut()).into();
+            let mut children: Vec<Handle> = Vec::with_capacity(queue.len());
+            while let Some(child) = queue.pop_front() {
+                if self.is_highlight(&child) {
+                    for grandchild in mem::take(&mut *child.children.borrow_mut())
+                        .into_iter()
+                        .rev()
                     {
-                        // node should be a TextNode and so have no children to check so OK to
-                        // continue here
-                        for new_child_node in value.iter() {
-                            dom.append(&parent, NodeOrText::AppendNode(new_child_node.clone()));
-                        }
-                        removed.push(node);
-                        continue;
-                    };
-                };
-                dom.append(&parent.clone(), NodeOrText::AppendNode(node.clone()));
-            } else {
-                for sub in node.children.borrow_mut().iter_mut() {
-                    sub.parent.replace(Some(Rc::downgrade(&parent)));
+                        queue.push_front(grandchild);
+                    }
+                    continue;
+                }
+                if let (
+                    Some(Node {
+                        data: NodeData::Text { contents: previous },
+                        ..
+                    }),
+                    NodeData::Text { ref contents },
+                ) = (children.last().map(|handle| &**handle), &child.data)
+                {
+                    previous.borrow_mut().push_tendril(&contents.borrow());
+                    continue;
+                }
+                self.strip_attribute_annotations(&child);
+                child.parent.set(Some(Rc::downgrade(&node)));
+                stack.push(child.clone());
+                children.push(child);
+            }
+            *node.children.borrow_mut() = children;
+        }
+    }
+
+    fn strip_attribute_annotations(&self, node: &Handle) {
+        if let NodeData::Element { ref attrs, .. } = node.data {
+            let mut attrs = attrs.borrow_mut();
+            let annotated = attrs.len();
+            attrs.retain(|attr| &*attr.name.local != ATTRIBUTE_MATCH_ATTRIBUTE);
+            if attrs.len() == annotated {
+                return;
+            }
+            if let Some(class) = self.attribute_match_class {
+                if let Some(index) = attrs
+                    .iter()
+                    .position(|attr| attr.name.local == local_name!("class"))
+                {
+                    let remaining = attrs[index]
+                        .value
+                        .split_ascii_whitespace()
+                        .filter(|value| *value != class)
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    if remaining.is_empty() {
+                        attrs.remove(index);
+                    } else {
+                        attrs[index].value = remaining.into();
+                    }
                 }
             }
-            stack.extend(
-                mem::take(&mut *node.children.borrow_mut())
-                    .into_iter()
-                    .rev(),
-            );
         }
-        while let Some(node) = removed.pop() {
-            removed.extend_from_slice(&mem::take(&mut *node.children.borrow_mut())[..]);
+    }
+
+    /// Removes highlights previously inserted by searchlite from parsed HTML.
+    pub fn unhighlight_dom(&self, html: Html) -> Document {
+        let dom = html.0;
+        self.strip_highlights(&dom.document);
+        Document {
+            dom,
+            statistics: MatchStatistics::default(),
         }
-        Document(dom)
     }
 
+    /// Parses `src` and removes highlights previously inserted by searchlite.
+    pub fn unhighlight(&self, src: &str) -> Document {
+        self.unhighlight_dom(Html::parse(src))
+    }
+
+    /// Highlights matches of the search term in parsed HTML. Highlights left over from an
+    /// earlier run are removed first, so highlighting already-highlighted HTML does not nest or
+    /// duplicate `<mark>` elements.
+    pub fn search_dom(&self, html: Html) -> Document {
+        let dom = html.0;
+        self.strip_highlights(&dom.document);
+        let (statistics, _) = self.highlight_searchables(&mut self.searchables(&dom.root()), false);
+        Document { dom, statistics }
+    }
+
+    /// Parses `src` as an HTML fragment and highlights matches of the search term.
     pub fn search(&self, src: &str) -> Document {
-        let parser = Self::make_parser();
-        let dom = parser.one(src);
-        self.search_dom(dom)
+        self.search_dom(Html::parse(src))
+    }
+
+    /// Parses `src` as a full HTML document (including doctype, `<head>` and `<body>`) and
+    /// highlights matches of the search term in it.
+    pub fn search_document(&self, src: &str) -> Document {
+        self.search_dom(Html::parse_document(src))
+    }
+
+    /// Highlights matches of the search term in plain text, such as a log line or search result
+    /// excerpt. `text` is never parsed as HTML: serialising the result escapes `&`, `<` and `>`
+    /// in it, so only the highlight elements are markup.
+    pub fn search_text(&self, text: &str) -> Document {
+        let mut dom = Self::make_parser().one("");
+        let root = dom.root();
+        dom.append(&root, NodeOrText::AppendText(text.into()));
+        self.search_dom(Html(dom))
     }
 
     /*
-     * Searches text content within `child` for the search term. Returns `None` if no match is
-     * found and returns `Some(replacement)` if a match is found. `replacement` will have occurrences
-     * of the search term wrapped in a `<mark>` tag.
+     * The names of the attributes in `attrs` listed in `match_attributes` which contain the
+     * search term, if any do.
      */
-    fn replacement_node(
-        &self,
-        child: &mut Handle,
-        dom: &mut RcDom,
-        already_matched: &mut bool,
-    ) -> Option<Vec<Rc<Node>>> {
-        let mut replacement_nodes = Vec::new();
-        if let NodeData::Text { ref contents, .. } = child.data {
-            let search_pattern: Vec<&str> = self.search_term?.split(' ').collect();
-            let ac = AhoCorasickBuilder::new()
-                .ascii_case_insensitive(true)
-                .build(search_pattern);
-            let mut matches = vec![];
-            let search_content = contents.borrow();
-            for search_term_match in ac.find_iter(&search_content[..]) {
-                matches.push((search_term_match.start(), search_term_match.end()));
-            }
-            let mut index: usize = 0;
-            for (start, end) in matches.iter() {
-                replacement_nodes.push(Node::new(NodeData::Text {
-                    contents: RefCell::new(search_content[index..*start].into()),
-                }));
-                let new_mark_node_text = Node::new(NodeData::Text {
-                    contents: RefCell::new(search_content[*start..*end].into()),
-                });
-                let new_mark_node = if *already_matched {
-                    Node::new(NodeData::Element {
-                        name: QualName::new(None, ns!(), local_name!("mark")),
-                        attrs: RefCell::new(vec![]),
-                        template_contents: RefCell::new(None),
-                        mathml_annotation_xml_integration_point: false,
-                    })
-                } else {
-                    let search_attribute = Attribute {
-                        name: QualName::new(None, ns!(), local_name!("id")),
-                        value: "search-match".into(),
-                    };
-                    *already_matched = true;
-                    Node::new(NodeData::Element {
-                        name: QualName::new(None, ns!(), local_name!("mark")),
-                        attrs: RefCell::new(vec![search_attribute]),
-                        template_contents: RefCell::new(None),
-                        mathml_annotation_xml_integration_point: false,
-                    })
-                };
-                dom.append(&new_mark_node, NodeOrText::AppendNode(new_mark_node_text));
-                replacement_nodes.push(new_mark_node);
-                index = *end;
-            }
-            replacement_nodes.push(Node::new(NodeData::Text {
-                contents: RefCell::new(search_content[index..].into()),
-            }));
-            if replacement_nodes.is_empty() {
-                return None;
-            } else {
-                return Some(replacement_nodes);
+    fn attribute_match(&self, attrs: &[Attribute], matcher: &Matcher) -> Option<String> {
+        let matched: Vec<&str> = attrs
+            .iter()
+            .filter(|attr| {
+                self.match_attributes.contains(&*attr.name.local) && matcher.is_match(&attr.value)
+            })
+            .map(|attr| &*attr.name.local)
+            .collect();
+        (!matched.is_empty()).then(|| matched.join(" "))
+    }
+
+    /*
+     * Marks an element with a `data-searchlite-attr-match` attribute listing the attributes in
+     * `matched` (and the attribute match class, if set).
+     */
+    fn annotate_attribute_match(&self, attrs: &mut Vec<Attribute>, matched: &str) {
+        let annotation = Attribute {
+            name: QualName::new(None, ns!(), ATTRIBUTE_MATCH_ATTRIBUTE.into()),
+            value: matched.into(),
+        };
+        if let Some(class) = self.attribute_match_class {
+            match attrs
+                .iter_mut()
+                .find(|attr| attr.name.local == local_name!("class"))
+            {
+                Some(attr) => {
+                    attr.value.push_char(' ');
+                    attr.value.push_slice(class);
+                }
+                None => attrs.push(Attribute {
+                    name: QualName::new(None, ns!(), local_name!("class")),
+                    value: class.into(),
+                }),
             }
         }
-        None
+        attrs.push(annotation);
+    }
+
+    /*
+     * Whether another match of the term at `term_index` may be highlighted without going over
+     * the highlight limits.
+     */
+    fn can_highlight(&self, term_index: usize, statistics: &MatchStatistics) -> bool {
+        self.max_highlights
+            .is_none_or(|limit| statistics.highlighted < limit)
+            && self
+                .max_highlights_per_term
+                .is_none_or(|limit| statistics.terms[term_index].highlighted < limit)
+    }
+
+    fn highlight_element(&self, first_match: bool, term: &Term) -> Handle {
+        let mut attrs = Vec::new();
+        if first_match {
+            attrs.push(Attribute {
+                name: QualName::new(None, ns!(), local_name!("id")),
+                value: FIRST_MATCH_ID.into(),
+            });
+        }
+        if let Some(class) = self.highlight_class(term.class) {
+            attrs.push(Attribute {
+                name: QualName::new(None, ns!(), local_name!("class")),
+                value: class.into(),
+            });
+        }
+        let (name, value) = self.marker_attribute;
+        attrs.push(Attribute {
+            name: QualName::new(None, ns!(), name.into()),
+            value: value.into(),
+        });
+        Node::new(NodeData::Element {
+            name: QualName::new(None, ns!(html), self.highlight_tag.into()),
+            attrs: RefCell::new(attrs),
+            template_contents: RefCell::new(None),
+            mathml_annotation_xml_integration_point: false,
+        })
     }
 
-    pub fn make_parser() -> driver::Parser<RcDom> {
+    /// Parser for the HTML fragments searchlite works on.
+    pub(crate) fn make_parser() -> driver::Parser<RcDom> {
         driver::parse_fragment(
-            RcDom::default(),
+            RcDom::fragment(),
             driver::ParseOpts::default(),
             QualName::new(None, ns!(html), local_name!("div")),
             vec![],
         )
     }
+
+    /// Parser for full HTML documents.
+    pub(crate) fn make_document_parser() -> driver::Parser<RcDom> {
+        driver::parse_document(RcDom::default(), driver::ParseOpts::default())
+    }
 }
 
-pub struct Document(RcDom);
+/// HTML parsed once, ready to search as often as needed with [`Builder::search_dom`]. Cloning
+/// copies the parsed tree without parsing it again, so one parse can serve many searches.
+#[derive(Clone)]
+pub struct Html(RcDom);
 
-impl Document {
-    fn serialize_opts() -> SerializeOpts {
-        SerializeOpts::default()
+impl Html {
+    /// Parses `src` as an HTML fragment, as [`Builder::search`] does.
+    pub fn parse(src: &str) -> Self {
+        Html(Builder::make_parser().one(src))
+    }
+
+    /// Parses `src` as a full HTML document, as [`Builder::search_document`] does.
+    pub fn parse_document(src: &str) -> Self {
+        Html(Builder::make_document_parser().one(src))
+    }
+
+    /// Parse errors and quirks mode html5ever reported while parsing.
+    pub fn diagnostics(&self) -> Diagnostics {
+        Diagnostics::from(&self.0)
     }
 }
 
-impl Clone for Document {
-    fn clone(&self) -> Self {
-        let parser = Builder::make_parser();
-        let dom = parser.one(&self.to_string()[..]);
-        Document(dom)
+/// A parsed (and possibly highlighted) HTML fragment. Use its [`Display`] implementation, or
+/// `to_string`, to serialise it back to HTML. Cloning copies the DOM without parsing it again.
+#[derive(Clone)]
+pub struct Document {
+    dom: RcDom,
+    statistics: MatchStatistics,
+}
+
+impl Document {
+    /// Serialises the document back to markup as `options` say. [`Display`] gives the same as
+    /// the default options.
+    pub fn serialize(&self, options: &SerializationOptions) -> String {
+        let traversal_scope = match options.scope {
+            SerializationScope::IncludeRoot if self.dom.is_fragment() => {
+                TraversalScope::IncludeNode
+            }
+            _ => TraversalScope::ChildrenOnly(None),
+        };
+        let mut ret_val = Vec::new();
+        let mut serializer = MarkupSerializer::new(&mut ret_val, *options);
+        let inner: SerializableHandle = self.dom.root().into();
+        inner
+            .serialize(&mut serializer, traversal_scope)
+            .and_then(|()| serializer.finish())
+            .expect("Writing to a string shouldn't fail (expect on OOM)");
+        String::from_utf8(ret_val).expect("html5ever only supports UTF8")
+    }
+
+    /// Parse errors and quirks mode html5ever reported while parsing the source HTML.
+    pub fn diagnostics(&self) -> Diagnostics {
+        Diagnostics::from(&self.dom)
+    }
+
+    /// How many matches were found, overall and per search term.
+    pub fn statistics(&self) -> &MatchStatistics {
+        &self.statistics
     }
 }
 
 impl Display for Document {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let opts = Self::serialize_opts();
-        let mut ret_val = Vec::new();
-        let inner: SerializableHandle = self.0.document.children.borrow()[0].clone().into();
-        serialize(&mut ret_val, &inner, opts)
-            .expect("Writing to a string shouldn't fail (expect on OOM)");
-        String::from_utf8(ret_val)
-            .expect("html5ever only supports UTF8")
-            .fmt(f)
+        self.serialize(&SerializationOptions::default()).fmt(f)
     }
 }
 
+/// Highlights `search_term` (space-separated terms) in `html`, returning the serialised result.
 pub fn search_html(html: &str, search_term: &str) -> String {
     Builder::new()
         .search_term(Some(search_term))
         .search(html)
         .to_string()
 }
+
+/// Highlights `search_term` (space-separated terms) in plain `text`, returning HTML with the text
+/// escaped.
+pub fn highlight_text(text: &str, search_term: &str) -> String {
+    Builder::new()
+        .search_term(Some(search_term))
+        .search_text(text)
+        .to_string()
+}
+
+/// Removes highlights searchlite previously inserted into `html`.
+pub fn remove_highlights(html: &str) -> String {
+    Builder::new().unhighlight(html).to_string()
+}
@@ -0,0 +1,60 @@
+use super::folding::FoldedText;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "wasm")]
+use tsify::Tsify;
+use unicode_normalization::{char::canonical_combining_class, UnicodeNormalization};
+
+/// Unicode normalisation form applied to search terms and searched text before matching, so
+/// that differently encoded versions of the same text match each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub enum Normalization {
+    /// Canonical composition: `e` followed by a combining acute accent matches `é`.
+    Nfc,
+    /// Compatibility composition: as `Nfc`, and also full-width `ＡＰＰＬＥ` matches `APPLE`
+    /// and the ligature `ﬁ` matches `fi`.
+    Nfkc,
+}
+
+/*
+ * Whether `c` may combine with the characters before it, so must be normalised along with them:
+ * combining marks, and Hangul vowel and final consonant jamo.
+ */
+fn continues_run(c: char) -> bool {
+    canonical_combining_class(c) != 0 || matches!(c, '\u{1160}'..='\u{11FF}')
+}
+
+/// Normalises `text` a run at a time, each run being a character followed by any characters
+/// which combine with it, so every run of the output maps back onto whole original characters.
+pub(crate) fn normalize(text: &str, form: Normalization) -> FoldedText {
+    let mut folded = FoldedText::with_capacity(text.len());
+    let mut normalized = String::new();
+    let mut run_start = 0;
+    let ends = text
+        .char_indices()
+        .skip(1)
+        .filter(|&(_, c)| !continues_run(c))
+        .map(|(index, _)| index)
+        .chain([text.len()]);
+    for run_end in ends {
+        let run = &text[run_start..run_end];
+        if run.is_ascii() {
+            folded.push(run, run_start..run_end);
+        } else {
+            normalized.clear();
+            match form {
+                Normalization::Nfc => normalized.extend(run.nfc()),
+                Normalization::Nfkc => normalized.extend(run.nfkc()),
+            }
+            folded.push(&normalized, run_start..run_end);
+        }
+        run_start = run_end;
+    }
+    folded
+}
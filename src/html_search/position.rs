@@ -0,0 +1,59 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "wasm")]
+use tsify::Tsify;
+
+/// An offset into a string, counted three ways: UTF-8 bytes for Rust strings, UTF-16 code units
+/// for JavaScript strings and DOM APIs, and Unicode scalar values (`char`s), which match what
+/// users see as characters more closely (for example, in Python string indices).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "camelCase"))]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct Offset {
+    pub bytes: usize,
+    pub utf16: usize,
+    pub chars: usize,
+}
+
+impl Offset {
+    /// This offset moved past `text`.
+    pub(crate) fn advance(self, text: &str) -> Self {
+        Offset {
+            bytes: self.bytes + text.len(),
+            utf16: self.utf16 + text.encode_utf16().count(),
+            chars: self.chars + text.chars().count(),
+        }
+    }
+
+    /// How far this offset is past `start`, an earlier offset into the same string.
+    pub(crate) fn since(self, start: Offset) -> Self {
+        Offset {
+            bytes: self.bytes - start.bytes,
+            utf16: self.utf16 - start.utf16,
+            chars: self.chars - start.chars,
+        }
+    }
+}
+
+/*
+ * Converts byte offsets in `text`, which must not decrease from one call to the next, into
+ * `Offset`s, counting on from the previous call rather than from the start each time.
+ */
+pub(crate) struct OffsetCounter<'t> {
+    text: &'t str,
+    offset: Offset,
+}
+
+impl<'t> OffsetCounter<'t> {
+    pub fn new(text: &'t str) -> Self {
+        OffsetCounter {
+            text,
+            offset: Offset::default(),
+        }
+    }
+
+    pub fn at(&mut self, bytes: usize) -> Offset {
+        self.offset = self.offset.advance(&self.text[self.offset.bytes..bytes]);
+        self.offset
+    }
+}
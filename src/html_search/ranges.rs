@@ -0,0 +1,176 @@
+use super::{
+    budget::Budget,
+    dom::{Handle, NodeData, RcDom},
+    position::{Offset, OffsetCounter},
+    runs::{run_length, TextRun},
+    source::{SourceMap, SourceSpan},
+    Builder, Html, MatchStatistics,
+};
+use html5ever::tendril::TendrilSink;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "wasm")]
+use tsify::Tsify;
+
+/// Where a match is in the DOM, for highlighting it without changing the markup, as with the CSS
+/// Custom Highlight API: the match runs from `start` to `end` in the text node reached by
+/// following `path` down from the root. A match over text split by a comment has a range in each
+/// text node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "camelCase"))]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct HighlightRange {
+    /// Child indices (counting every node, as `childNodes` does) from the element the fragment
+    /// was parsed into, or from the document node for a full document, to the text node.
+    pub path: Vec<usize>,
+    /// Offset of the start of the match in the text node. Use its UTF-16 count with
+    /// `Range.setStart`.
+    pub start: Offset,
+    /// Offset of the end of the match in the text node.
+    pub end: Offset,
+    /// Index of the matching term in [`MatchStatistics::terms`].
+    pub term: usize,
+    /// Where the match was written in the input, when the ranges were found by parsing it with
+    /// [`Builder::ranges`] or [`Builder::ranges_document`] and its text could be found there.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub source: Option<SourceSpan>,
+}
+
+/// Matches found by [`Builder::ranges`], with match counts. Attribute matches are not counted,
+/// since a range cannot point into an attribute.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "camelCase"))]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct HighlightRanges {
+    pub ranges: Vec<HighlightRange>,
+    pub statistics: MatchStatistics,
+}
+
+impl<'a> Builder<'a> {
+    /// Parses `src` as an HTML fragment and finds matches of the search term, returning where
+    /// they are rather than inserting highlight elements. The highlight limits and budgets apply
+    /// as they do to [`Builder::search`].
+    /// Each range also gives the span of `src` the match was parsed from, for decorating the
+    /// source itself.
+    pub fn ranges(&self, src: &str) -> HighlightRanges {
+        let dom = Self::make_parser().one(src);
+        self.find_ranges(&dom, Some(&SourceMap::new(src, &dom)))
+    }
+
+    /// Like [`Builder::ranges`], parsing `src` as a full document.
+    pub fn ranges_document(&self, src: &str) -> HighlightRanges {
+        let dom = Self::make_document_parser().one(src);
+        self.find_ranges(&dom, Some(&SourceMap::new(src, &dom)))
+    }
+
+    /// Like [`Builder::ranges`], for already-parsed HTML, which is left unchanged. Ranges have
+    /// no source spans, since the source is not known.
+    pub fn ranges_dom(&self, html: &Html) -> HighlightRanges {
+        self.find_ranges(&html.0, None)
+    }
+
+    pub(super) fn find_ranges(&self, dom: &RcDom, source: Option<&SourceMap>) -> HighlightRanges {
+        let (parts, statistics) = self.find_range_parts(dom, source);
+        HighlightRanges {
+            ranges: parts.into_iter().map(|(range, _)| range).collect(),
+            statistics,
+        }
+    }
+
+    /*
+     * Finds ranges as `find_ranges` does, each with whether it continues the match of the range
+     * before it: a match over text split by a comment has a range in each text node.
+     */
+    pub(super) fn find_range_parts(
+        &self,
+        dom: &RcDom,
+        source: Option<&SourceMap>,
+    ) -> (Vec<(HighlightRange, bool)>, MatchStatistics) {
+        let Some(matcher) = self.matcher() else {
+            return (Vec::new(), MatchStatistics::default());
+        };
+        let mut statistics = MatchStatistics::new(matcher.terms().iter().map(|term| term.text));
+        let mut ranges = Vec::new();
+        let mut budget = Budget::new(self.node_budget, self.time_budget);
+        let mut stack: Vec<(Vec<Handle>, Vec<usize>)> = child_visits(&dom.root(), &[]);
+        while let Some((nodes, path)) = stack.pop() {
+            if !budget.spend_nodes(nodes.len()) {
+                statistics.budget_exhausted = true;
+                break;
+            }
+            let node = &nodes[0];
+            match node.data {
+                NodeData::Text { .. } => {
+                    let run = TextRun::new(&nodes);
+                    // where each node's text starts in the run's text
+                    let mut node_offsets = Vec::with_capacity(nodes.len());
+                    let mut offset = Offset::default();
+                    for index in 0..nodes.len() {
+                        node_offsets.push(offset);
+                        offset = offset.advance(&run.text[run.node_text(index)]);
+                    }
+                    let mut offsets = OffsetCounter::new(&run.text);
+                    for search_match in matcher.find(&run.text) {
+                        statistics.record(search_match.term);
+                        if !self.can_highlight(search_match.term, &statistics) {
+                            continue;
+                        }
+                        statistics.record_highlight(search_match.term);
+                        let match_start = offsets.at(search_match.start);
+                        for (part, (index, range)) in
+                            run.parts(search_match.start..search_match.end).enumerate()
+                        {
+                            let node_text = &run.text[run.node_text(index)];
+                            let start = if part == 0 {
+                                match_start.since(node_offsets[index])
+                            } else {
+                                Offset::default()
+                            };
+                            let mut part_path = path.clone();
+                            *part_path.last_mut().expect("a node's path is never empty") += index;
+                            ranges.push((
+                                HighlightRange {
+                                    path: part_path,
+                                    start,
+                                    end: start.advance(&node_text[range.clone()]),
+                                    term: search_match.term,
+                                    source: source.and_then(|source| {
+                                        source.span(&nodes[index], range.start, range.end)
+                                    }),
+                                },
+                                part > 0,
+                            ));
+                        }
+                    }
+                }
+                NodeData::Element { .. } if self.search_children(node) => {
+                    stack.extend(child_visits(node, &path));
+                }
+                _ => {}
+            }
+        }
+        (ranges, statistics)
+    }
+}
+
+/*
+ * The children of `node`, each with its path, in reverse order for popping off a stack. A run
+ * of text is visited as a whole, with the path of its first node.
+ */
+fn child_visits(node: &Handle, path: &[usize]) -> Vec<(Vec<Handle>, Vec<usize>)> {
+    let children = node.children.borrow();
+    let mut visits = Vec::new();
+    let mut index = 0;
+    while index < children.len() {
+        let length = match children[index].data {
+            NodeData::Text { .. } => run_length(&children[index..]),
+            _ => 1,
+        };
+        let mut child_path = path.to_vec();
+        child_path.push(index);
+        visits.push((children[index..index + length].to_vec(), child_path));
+        index += length;
+    }
+    visits.reverse();
+    visits
+}
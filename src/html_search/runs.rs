@@ -0,0 +1,482 @@
+use super::{
+    budget::Budget,
+    dom::{append, Handle, Node, NodeData},
+    matcher::{Match, Matcher},
+    Builder, MatchStatistics,
+};
+use html5ever::Attribute;
+use std::{cell::RefCell, ops::Range, rc::Rc};
+
+/*
+ * The number of nodes in the run of text starting at `siblings[0]`: the text nodes from there
+ * on with only comments between them, as a comment in the middle of a sentence leaves it. A
+ * run is searched as one text, so a phrase matches across the comments.
+ */
+pub(super) fn run_length(siblings: &[Handle]) -> usize {
+    let mut length = 0;
+    for (index, node) in siblings.iter().enumerate() {
+        match node.data {
+            NodeData::Text { .. } => length = index + 1,
+            NodeData::Comment { .. } => {}
+            _ => break,
+        }
+    }
+    length
+}
+
+/*
+ * A run of text, as `run_length` finds one, with the text of its nodes joined.
+ */
+pub(super) struct TextRun {
+    pub nodes: Vec<Handle>,
+    pub text: String,
+    // for each node, where its text starts in `text`; comments add nothing to it
+    starts: Vec<usize>,
+}
+
+impl TextRun {
+    pub fn new(nodes: &[Handle]) -> Self {
+        let mut text = String::new();
+        let mut starts = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            starts.push(text.len());
+            if let NodeData::Text { ref contents } = node.data {
+                text.push_str(&contents.borrow());
+            }
+        }
+        TextRun {
+            nodes: nodes.to_vec(),
+            text,
+            starts,
+        }
+    }
+
+    /*
+     * Where the text of the node at `index` is in `text`.
+     */
+    pub fn node_text(&self, index: usize) -> Range<usize> {
+        let end = self
+            .starts
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        self.starts[index]..end
+    }
+
+    /*
+     * The parts of `range` of `text` in each node it covers: the node's index and the part's
+     * offsets in the node's own text.
+     */
+    pub fn parts(&self, range: Range<usize>) -> impl Iterator<Item = (usize, Range<usize>)> + '_ {
+        (0..self.nodes.len()).filter_map(move |index| {
+            let node_text = self.node_text(index);
+            let start = range.start.max(node_text.start);
+            let end = range.end.min(node_text.end);
+            (start < end).then(|| (index, start - node_text.start..end - node_text.start))
+        })
+    }
+}
+
+/*
+ * The text and attributes searched in a DOM, in document order, with what was last highlighted
+ * in each, so that searching again only changes the nodes whose highlights change.
+ */
+pub(super) struct Searchables {
+    items: Vec<Searchable>,
+    // nodes walked over after the last item
+    trailing_nodes: usize,
+    // elements whose children were walked, numbered to keep track of where runs are in them
+    parents: usize,
+}
+
+struct Searchable {
+    target: Target,
+    // nodes walked over since the previous item, counting this one's
+    nodes: usize,
+    // whether the last search to look at this item found a match
+    matched: bool,
+}
+
+enum Target {
+    Text(HighlightedRun),
+    Attributes {
+        element: Handle,
+        // the attributes as parsed, without an annotation
+        original: Vec<Attribute>,
+        // the names of the matching attributes, when the element is annotated
+        annotation: Option<String>,
+    },
+}
+
+struct HighlightedRun {
+    run: TextRun,
+    parent: Handle,
+    parent_number: usize,
+    // where the run's current nodes start among the parent's children
+    index: usize,
+    // the nodes in the parent in place of the run: its own nodes, unless it is highlighted
+    current: Vec<Handle>,
+    highlights: Vec<Highlight>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Highlight {
+    start: usize,
+    end: usize,
+    term: usize,
+    first: bool,
+}
+
+impl Searchables {
+    /*
+     * Puts back every run and element as it was before it was highlighted.
+     */
+    pub fn clear(&mut self) {
+        let mut shifts: Vec<isize> = vec![0; self.parents];
+        for item in self.items.iter_mut() {
+            item.matched = false;
+            match item.target {
+                Target::Text(ref mut highlighted) => {
+                    highlighted.follow(&shifts);
+                    if !highlighted.highlights.is_empty() {
+                        let nodes = highlighted.run.nodes.clone();
+                        highlighted.show(nodes, Vec::new(), &mut shifts);
+                    }
+                }
+                Target::Attributes {
+                    ref element,
+                    ref original,
+                    ref mut annotation,
+                } => {
+                    if annotation.is_some() {
+                        set_attributes(element, original.clone());
+                        *annotation = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl HighlightedRun {
+    /*
+     * Moves the run's index past the changes before it in its parent, which `shifts` holds.
+     */
+    fn follow(&mut self, shifts: &[isize]) {
+        self.index = self
+            .index
+            .checked_add_signed(shifts[self.parent_number])
+            .expect("a run will not move before the start of its parent");
+    }
+
+    /*
+     * Puts `nodes`, which show `highlights`, in place of the run's current nodes, adding how far
+     * that moves the rest of its parent's children to `shifts`.
+     */
+    fn show(&mut self, nodes: Vec<Handle>, highlights: Vec<Highlight>, shifts: &mut [isize]) {
+        replace_children(
+            &self.parent,
+            self.index..self.index + self.current.len(),
+            &nodes,
+        );
+        shifts[self.parent_number] += nodes.len() as isize - self.current.len() as isize;
+        self.current = nodes;
+        self.highlights = highlights;
+    }
+}
+
+impl<'a> Builder<'a> {
+    /*
+     * Walks the children of `root`, which has no highlights in it, for the text runs and
+     * elements with searched attributes to search.
+     */
+    pub(super) fn searchables(&self, root: &Handle) -> Searchables {
+        let mut items = Vec::new();
+        let mut walked = 0;
+        let mut parents = 1;
+        // elements whose children are being walked, with their number and the next child's index
+        let mut stack: Vec<(Handle, usize, usize)> = vec![(root.clone(), 0, 0)];
+        while let Some((parent, parent_number, index)) = stack.last().cloned() {
+            let (child, length) = {
+                let children = parent.children.borrow();
+                let Some(child) = children.get(index) else {
+                    stack.pop();
+                    continue;
+                };
+                let length = match child.data {
+                    NodeData::Text { .. } => run_length(&children[index..]),
+                    _ => 1,
+                };
+                (child.clone(), length)
+            };
+            walked += length;
+            if let Some((_, _, next)) = stack.last_mut() {
+                *next += length;
+            }
+            match child.data {
+                NodeData::Text { .. } => {
+                    let run = TextRun::new(&parent.children.borrow()[index..index + length]);
+                    items.push(Searchable {
+                        target: Target::Text(HighlightedRun {
+                            current: run.nodes.clone(),
+                            run,
+                            parent,
+                            parent_number,
+                            index,
+                            highlights: Vec::new(),
+                        }),
+                        nodes: walked,
+                        matched: false,
+                    });
+                    walked = 0;
+                }
+                NodeData::Element { ref attrs, .. } => {
+                    let attrs = attrs.borrow();
+                    if attrs
+                        .iter()
+                        .any(|attr| self.match_attributes.contains(&*attr.name.local))
+                    {
+                        items.push(Searchable {
+                            target: Target::Attributes {
+                                element: child.clone(),
+                                original: attrs.clone(),
+                                annotation: None,
+                            },
+                            nodes: walked,
+                            matched: false,
+                        });
+                        walked = 0;
+                    }
+                    drop(attrs);
+                    if self.search_children(&child) {
+                        stack.push((child, parents, 0));
+                        parents += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Searchables {
+            items,
+            trailing_nodes: walked,
+            parents,
+        }
+    }
+
+    /*
+     * Searches `searchables` and highlights the matches in place, changing only the runs and
+     * elements whose highlights differ from the last search's. A narrowed search only looks for
+     * matches in items which matched last time, leaving the others unhighlighted. Returns the
+     * statistics and how many nodes had their text or attributes searched, or were walked over
+     * on the way for a search which was not narrowed.
+     */
+    pub(super) fn highlight_searchables(
+        &self,
+        searchables: &mut Searchables,
+        narrow: bool,
+    ) -> (MatchStatistics, usize) {
+        let matcher = self.matcher();
+        let mut statistics = MatchStatistics::new(
+            matcher
+                .iter()
+                .flat_map(|matcher| matcher.terms().iter().map(|term| term.text)),
+        );
+        let mut budget = Budget::new(self.node_budget, self.time_budget);
+        let mut searched_nodes = 0;
+        // how far the runs of each parent have moved, from highlights changing before them
+        let mut shifts: Vec<isize> = vec![0; searchables.parents];
+
+        for item in searchables.items.iter_mut() {
+            if !statistics.budget_exhausted && !budget.spend_nodes(item.nodes) {
+                statistics.budget_exhausted = true;
+            }
+            let matcher = matcher
+                .as_ref()
+                .filter(|_| !statistics.budget_exhausted && (!narrow || item.matched));
+            match item.target {
+                Target::Text(ref mut highlighted) => {
+                    highlighted.follow(&shifts);
+                    let highlights = match matcher {
+                        Some(matcher) => {
+                            searched_nodes += if narrow {
+                                highlighted.run.nodes.len()
+                            } else {
+                                item.nodes
+                            };
+                            let matches = matcher.find(&highlighted.run.text);
+                            item.matched = !matches.is_empty();
+                            self.highlights(&matches, &mut statistics)
+                        }
+                        None => Vec::new(),
+                    };
+                    if highlights != highlighted.highlights {
+                        let nodes = self.highlighted_run(&highlighted.run, &highlights, matcher);
+                        highlighted.show(nodes, highlights, &mut shifts);
+                    }
+                }
+                Target::Attributes {
+                    ref element,
+                    ref original,
+                    ref mut annotation,
+                } => {
+                    let matched = matcher.and_then(|matcher| {
+                        searched_nodes += if narrow { 1 } else { item.nodes };
+                        self.attribute_match(original, matcher)
+                    });
+                    if matcher.is_some() {
+                        item.matched = matched.is_some();
+                        statistics.attribute_matches += usize::from(item.matched);
+                    }
+                    if matched != *annotation {
+                        let mut attrs = original.clone();
+                        if let Some(ref matched) = matched {
+                            self.annotate_attribute_match(&mut attrs, matched);
+                        }
+                        set_attributes(element, attrs);
+                        *annotation = matched;
+                    }
+                }
+            }
+        }
+        if !statistics.budget_exhausted && !budget.spend_nodes(searchables.trailing_nodes) {
+            statistics.budget_exhausted = true;
+        }
+        if !narrow && !statistics.budget_exhausted {
+            searched_nodes += searchables.trailing_nodes;
+        }
+        (statistics, searched_nodes)
+    }
+
+    /*
+     * Counts `matches` and decides which of them to highlight, within the highlight limits.
+     */
+    fn highlights(&self, matches: &[Match], statistics: &mut MatchStatistics) -> Vec<Highlight> {
+        matches
+            .iter()
+            .filter_map(|search_match| {
+                statistics.record(search_match.term);
+                if !self.can_highlight(search_match.term, statistics) {
+                    return None;
+                }
+                let first = statistics.highlighted == 0;
+                statistics.record_highlight(search_match.term);
+                Some(Highlight {
+                    start: search_match.start,
+                    end: search_match.end,
+                    term: search_match.term,
+                    first,
+                })
+            })
+            .collect()
+    }
+
+    /*
+     * The nodes to put in place of `run` to show `highlights`: a highlight over more than one
+     * text node is split into a highlight element in each, only the first of which can be the
+     * first match. Comments and text nodes without a highlight are kept as they are.
+     */
+    fn highlighted_run(
+        &self,
+        run: &TextRun,
+        highlights: &[Highlight],
+        matcher: Option<&Matcher>,
+    ) -> Vec<Handle> {
+        let Some(matcher) = matcher.filter(|_| !highlights.is_empty()) else {
+            return run.nodes.clone();
+        };
+        let mut nodes = Vec::new();
+        for (index, node) in run.nodes.iter().enumerate() {
+            let node_text = run.node_text(index);
+            let parts: Vec<Highlight> = highlights
+                .iter()
+                .filter_map(|highlight| {
+                    let start = highlight.start.max(node_text.start);
+                    let end = highlight.end.min(node_text.end);
+                    (start < end).then(|| Highlight {
+                        start: start - node_text.start,
+                        end: end - node_text.start,
+                        term: highlight.term,
+                        first: highlight.first && highlight.start >= node_text.start,
+                    })
+                })
+                .collect();
+            if parts.is_empty() {
+                nodes.push(node.clone());
+            } else {
+                nodes.extend(self.highlighted_text(&run.text[node_text], &parts, matcher));
+            }
+        }
+        nodes
+    }
+
+    /*
+     * The nodes to put in place of a text node containing `text` to show `highlights`, which
+     * are either disjoint or nested.
+     */
+    fn highlighted_text(
+        &self,
+        text: &str,
+        highlights: &[Highlight],
+        matcher: &Matcher,
+    ) -> Vec<Handle> {
+        let text_node = |range: Range<usize>| {
+            Node::new(NodeData::Text {
+                contents: RefCell::new(text[range].into()),
+            })
+        };
+        let mut nodes = Vec::new();
+        // highlight elements which have been started, with the offset each one ends at; these
+        // always close in reverse order
+        let mut open: Vec<(Handle, usize)> = Vec::new();
+        let mut add = |open: &[(Handle, usize)], node: Handle| match open.last() {
+            Some((parent, _)) => append(parent, node),
+            None => nodes.push(node),
+        };
+        let mut index = 0;
+        for highlight in highlights {
+            while let Some(&(_, end)) = open.last() {
+                if end > highlight.start {
+                    break;
+                }
+                add(&open, text_node(index..end));
+                index = end;
+                open.pop();
+            }
+            add(&open, text_node(index..highlight.start));
+            index = highlight.start;
+            let element = self.highlight_element(highlight.first, &matcher.terms()[highlight.term]);
+            add(&open, element.clone());
+            open.push((element, highlight.end));
+        }
+        while let Some(&(_, end)) = open.last() {
+            add(&open, text_node(index..end));
+            index = end;
+            open.pop();
+        }
+        add(&open, text_node(index..text.len()));
+        nodes
+    }
+}
+
+fn set_attributes(element: &Handle, value: Vec<Attribute>) {
+    if let NodeData::Element { ref attrs, .. } = element.data {
+        *attrs.borrow_mut() = value;
+    }
+}
+
+/*
+ * Puts `nodes` in place of the children of `parent` in `range`.
+ */
+fn replace_children(parent: &Handle, range: Range<usize>, nodes: &[Handle]) {
+    let removed: Vec<Handle> = parent
+        .children
+        .borrow_mut()
+        .splice(range, nodes.iter().cloned())
+        .collect();
+    for node in removed {
+        node.parent.set(None);
+    }
+    for node in nodes {
+        node.parent.set(Some(Rc::downgrade(parent)));
+    }
+}
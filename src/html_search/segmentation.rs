@@ -0,0 +1,83 @@
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "wasm")]
+use tsify::Tsify;
+
+/// How the whitespace-separated terms of a [`super::Builder::search_term`] query are broken up
+/// further, for scripts which do not put spaces between words.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub enum Segmentation {
+    /// Terms are only split at whitespace.
+    #[default]
+    Whitespace,
+    /// Runs of Chinese, Japanese, Thai, Lao, Khmer or Myanmar characters are split from the
+    /// rest of a term and, when longer than two characters, broken into overlapping pairs of
+    /// characters (bigrams): `東京大学` is searched as `東京`, `京大` and `大学`. Matches of
+    /// bigrams which overlap in the text are highlighted as one. Characters of these scripts
+    /// also count as words on their own for [`super::Builder::whole_word`].
+    Bigram,
+}
+
+/// A piece of a query term, and whether it is a bigram taken from a longer run of characters.
+pub(crate) struct Segment<'t> {
+    pub text: &'t str,
+    pub bigram: bool,
+}
+
+/// Whether `c` belongs to a script which is written without spaces between words.
+pub(crate) fn is_unspaced(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0E00}'..='\u{0EFF}' // Thai, Lao
+            | '\u{1000}'..='\u{109F}' // Myanmar
+            | '\u{1780}'..='\u{17FF}' // Khmer
+            | '\u{3040}'..='\u{30FF}' // Hiragana, Katakana
+            | '\u{31F0}'..='\u{31FF}' // Katakana phonetic extensions
+            | '\u{3400}'..='\u{4DBF}' // CJK unified ideographs extension A
+            | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+            | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+            | '\u{FF66}'..='\u{FF9F}' // halfwidth Katakana
+            | '\u{20000}'..='\u{2FA1F}' // supplementary ideographs
+    )
+}
+
+/*
+ * Splits a whitespace-free query term into runs of unspaced and other characters, breaking
+ * unspaced runs of more than two characters into bigrams.
+ */
+pub(crate) fn segments(term: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = term;
+    while let Some(first) = rest.chars().next() {
+        let unspaced = is_unspaced(first);
+        let run_end = rest
+            .char_indices()
+            .find(|&(_, c)| is_unspaced(c) != unspaced)
+            .map_or(rest.len(), |(index, _)| index);
+        let (run, remainder) = rest.split_at(run_end);
+        rest = remainder;
+        let starts: Vec<usize> = run.char_indices().map(|(index, _)| index).collect();
+        if !unspaced || starts.len() <= 2 {
+            segments.push(Segment {
+                text: run,
+                bigram: false,
+            });
+            continue;
+        }
+        segments.extend(starts.windows(3).map(|window| Segment {
+            text: &run[window[0]..window[2]],
+            bigram: true,
+        }));
+        segments.push(Segment {
+            text: &run[starts[starts.len() - 2]..],
+            bigram: true,
+        });
+    }
+    segments
+}
@@ -0,0 +1,474 @@
+use html5ever::{
+    local_name, namespace_url, ns,
+    serialize::{AttrRef, Serializer},
+    LocalName, Namespace, QualName,
+};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+use std::io::{self, Write};
+#[cfg(feature = "wasm")]
+use tsify::Tsify;
+
+/// Markup syntax [`super::Document::serialize`] writes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub enum MarkupFormat {
+    /// HTML, as browsers serialise `innerHTML`.
+    #[default]
+    Html,
+    /// Well-formed XHTML, as EPUB needs: void elements are self-closed (`<br />`), `<`, `>` and
+    /// `&` are escaped everywhere (including in scripts and styles), no-break spaces are written
+    /// as `&#160;`, the outermost HTML, SVG and MathML elements declare their namespace (as
+    /// the outermost elements with `xlink:` and other prefixed attributes declare the prefix),
+    /// and hyphens in comments are spaced out where XML does not allow them (`--` and a final
+    /// `-`).
+    Xhtml,
+}
+
+/// How characters which must be escaped are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub enum EntityStyle {
+    /// Named character references, such as `&amp;` and `&nbsp;`.
+    #[default]
+    Named,
+    /// Decimal character references, such as `&#38;` and `&#160;`.
+    Numeric,
+}
+
+/// Which nodes [`super::Document::serialize`] writes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub enum SerializationScope {
+    /// The parsed content only.
+    #[default]
+    Children,
+    /// The parsed content inside the `<html>` element html5ever parses a fragment into. A full
+    /// document has no such element, so this changes nothing for one.
+    IncludeRoot,
+}
+
+/// Settings for [`super::Document::serialize`]. The defaults give the same output as
+/// [`std::fmt::Display`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(default, deny_unknown_fields, rename_all = "camelCase")
+)]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct SerializationOptions {
+    pub format: MarkupFormat,
+    pub entities: EntityStyle,
+    pub scope: SerializationScope,
+    /// Put block-level elements on lines of their own, indented by two spaces per level.
+    /// Whitespace-only text between them is replaced; other text, and the content of `<pre>`,
+    /// `<textarea>`, scripts and styles, is left as it is. Meant for reading, since the added
+    /// whitespace can occasionally change how a page renders.
+    pub pretty: bool,
+}
+
+/// Elements given lines of their own when pretty printing.
+const BLOCK_ELEMENTS: [LocalName; 47] = [
+    local_name!("address"),
+    local_name!("article"),
+    local_name!("aside"),
+    local_name!("base"),
+    local_name!("blockquote"),
+    local_name!("body"),
+    local_name!("caption"),
+    local_name!("col"),
+    local_name!("colgroup"),
+    local_name!("dd"),
+    local_name!("details"),
+    local_name!("dialog"),
+    local_name!("div"),
+    local_name!("dl"),
+    local_name!("dt"),
+    local_name!("fieldset"),
+    local_name!("figcaption"),
+    local_name!("figure"),
+    local_name!("footer"),
+    local_name!("form"),
+    local_name!("h1"),
+    local_name!("h2"),
+    local_name!("h3"),
+    local_name!("h4"),
+    local_name!("h5"),
+    local_name!("h6"),
+    local_name!("head"),
+    local_name!("header"),
+    local_name!("hr"),
+    local_name!("html"),
+    local_name!("li"),
+    local_name!("link"),
+    local_name!("main"),
+    local_name!("meta"),
+    local_name!("nav"),
+    local_name!("ol"),
+    local_name!("p"),
+    local_name!("pre"),
+    local_name!("section"),
+    local_name!("table"),
+    local_name!("tbody"),
+    local_name!("td"),
+    local_name!("tfoot"),
+    local_name!("th"),
+    local_name!("thead"),
+    local_name!("title"),
+    local_name!("tr"),
+];
+
+/// Elements whose content is written without pretty printing.
+const PREFORMATTED_ELEMENTS: [LocalName; 5] = [
+    local_name!("pre"),
+    local_name!("script"),
+    local_name!("style"),
+    local_name!("textarea"),
+    local_name!("title"),
+];
+
+/// Elements whose text is not escaped in HTML.
+const RAW_TEXT_ELEMENTS: [LocalName; 8] = [
+    local_name!("iframe"),
+    local_name!("noembed"),
+    local_name!("noframes"),
+    local_name!("noscript"),
+    local_name!("plaintext"),
+    local_name!("script"),
+    local_name!("style"),
+    local_name!("xmp"),
+];
+
+const VOID_ELEMENTS: [LocalName; 18] = [
+    local_name!("area"),
+    local_name!("base"),
+    local_name!("basefont"),
+    local_name!("bgsound"),
+    local_name!("br"),
+    local_name!("col"),
+    local_name!("embed"),
+    local_name!("frame"),
+    local_name!("hr"),
+    local_name!("img"),
+    local_name!("input"),
+    local_name!("keygen"),
+    local_name!("link"),
+    local_name!("meta"),
+    local_name!("param"),
+    local_name!("source"),
+    local_name!("track"),
+    local_name!("wbr"),
+];
+
+#[derive(Default)]
+struct ElementInfo {
+    name: Option<QualName>,
+    // whether the element's children are not written, as for void elements in HTML
+    ignore_children: bool,
+    // whether a block-level child was put on a line of its own
+    block_children: bool,
+    // namespace prefixes declared on the element, in XHTML
+    prefixes: Vec<(LocalName, Namespace)>,
+}
+
+/*
+ * The prefix written before the name of the attribute `name`, as html5ever writes them.
+ */
+fn attribute_prefix(name: &QualName) -> Option<&'static str> {
+    match name.ns {
+        ns!(xml) => Some("xml"),
+        ns!(xmlns) if name.local != local_name!("xmlns") => Some("xmlns"),
+        ns!(xlink) => Some("xlink"),
+        ns!() | ns!(xmlns) => None,
+        _ => Some("unknown_namespace"),
+    }
+}
+
+/*
+ * Writes markup as html5ever's `HtmlSerializer` does (matching it exactly with the default
+ * options), with the extra options applied.
+ */
+pub(crate) struct MarkupSerializer<W: Write> {
+    writer: W,
+    options: SerializationOptions,
+    stack: Vec<ElementInfo>,
+    // whether anything has been written yet
+    started: bool,
+    // whitespace-only text held back while pretty printing, until it is known whether it sits
+    // between block-level elements
+    pending_whitespace: String,
+}
+
+impl<W: Write> MarkupSerializer<W> {
+    pub fn new(writer: W, options: SerializationOptions) -> Self {
+        MarkupSerializer {
+            writer,
+            options,
+            stack: vec![ElementInfo::default()],
+            started: false,
+            pending_whitespace: String::new(),
+        }
+    }
+
+    /// Writes any whitespace held back for pretty printing.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.flush_whitespace()
+    }
+
+    fn parent(&self) -> &ElementInfo {
+        self.stack
+            .last()
+            .expect("the stack starts with an entry for the root")
+    }
+
+    fn in_html(&self, names: &[LocalName]) -> bool {
+        self.parent()
+            .name
+            .as_ref()
+            .is_some_and(|name| name.ns == ns!(html) && names.contains(&name.local))
+    }
+
+    fn preformatted(&self) -> bool {
+        self.stack.iter().any(|info| {
+            info.name.as_ref().is_some_and(|name| {
+                name.ns == ns!(html) && PREFORMATTED_ELEMENTS.contains(&name.local)
+            })
+        })
+    }
+
+    fn is_block(&self, name: &QualName) -> bool {
+        self.options.pretty
+            && name.ns == ns!(html)
+            && BLOCK_ELEMENTS.contains(&name.local)
+            && !self.preformatted()
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.started = true;
+        self.writer.write_all(bytes)
+    }
+
+    fn flush_whitespace(&mut self) -> io::Result<()> {
+        if self.pending_whitespace.is_empty() {
+            return Ok(());
+        }
+        let whitespace = std::mem::take(&mut self.pending_whitespace);
+        self.write(whitespace.as_bytes())
+    }
+
+    /*
+     * Starts a new line indented for an element `depth` levels down, dropping whitespace held
+     * back. Nothing is written at the very start of the output.
+     */
+    fn new_line(&mut self, depth: usize) -> io::Result<()> {
+        self.pending_whitespace.clear();
+        if !self.started {
+            return Ok(());
+        }
+        self.write(b"\n")?;
+        self.write("  ".repeat(depth).as_bytes())
+    }
+
+    fn write_escaped(&mut self, text: &str, attribute: bool) -> io::Result<()> {
+        let xhtml = self.options.format == MarkupFormat::Xhtml;
+        let numeric = self.options.entities == EntityStyle::Numeric;
+        let mut written = 0;
+        for (index, c) in text.char_indices() {
+            let escaped = match c {
+                '&' => Some(("&amp;", "&#38;")),
+                '\u{00A0}' if xhtml => Some(("&#160;", "&#160;")),
+                '\u{00A0}' => Some(("&nbsp;", "&#160;")),
+                '"' if attribute => Some(("&quot;", "&#34;")),
+                '<' if !attribute || xhtml => Some(("&lt;", "&#60;")),
+                '>' if !attribute || xhtml => Some(("&gt;", "&#62;")),
+                _ => None,
+            };
+            if let Some((named, decimal)) = escaped {
+                self.write(&text.as_bytes()[written..index])?;
+                self.write(if numeric { decimal } else { named }.as_bytes())?;
+                written = index + c.len_utf8();
+            }
+        }
+        self.write(&text.as_bytes()[written..])
+    }
+
+    fn write_attribute(&mut self, name: &QualName, value: &str) -> io::Result<()> {
+        self.write(b" ")?;
+        if let Some(prefix) = attribute_prefix(name) {
+            self.write(prefix.as_bytes())?;
+            self.write(b":")?;
+        }
+        self.write(name.local.as_bytes())?;
+        self.write(b"=\"")?;
+        self.write_escaped(value, true)?;
+        self.write(b"\"")
+    }
+}
+
+impl<W: Write> Serializer for MarkupSerializer<W> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        if self.parent().ignore_children {
+            self.stack.push(ElementInfo {
+                name: Some(name),
+                ignore_children: true,
+                block_children: false,
+                prefixes: Vec::new(),
+            });
+            return Ok(());
+        }
+        if self.is_block(&name) {
+            self.new_line(self.stack.len() - 1)?;
+            if let Some(parent) = self.stack.last_mut() {
+                parent.block_children = true;
+            }
+        } else {
+            self.flush_whitespace()?;
+        }
+
+        let xhtml = self.options.format == MarkupFormat::Xhtml;
+        self.write(b"<")?;
+        self.write(name.local.as_bytes())?;
+        let attrs: Vec<AttrRef> = attrs.collect();
+        let mut declares_namespace = false;
+        let mut prefixes = Vec::new();
+        for &(attribute_name, value) in &attrs {
+            declares_namespace |= attribute_name.local == local_name!("xmlns");
+            if attribute_name.ns == ns!(xmlns) && attribute_name.local != local_name!("xmlns") {
+                prefixes.push((attribute_name.local.clone(), Namespace::from(value)));
+            }
+            self.write_attribute(attribute_name, value)?;
+        }
+        // as does the outermost element with an attribute in each namespace other than `xml`
+        for &(attribute_name, _) in attrs.iter().filter(|_| xhtml) {
+            let prefix = match attribute_prefix(attribute_name) {
+                Some(prefix) if prefix != "xml" && prefix != "xmlns" => prefix,
+                _ => continue,
+            };
+            let in_scope = self
+                .stack
+                .iter()
+                .flat_map(|info| &info.prefixes)
+                .chain(&prefixes)
+                .rev()
+                .find(|(declared, _)| &**declared == prefix)
+                .is_some_and(|(_, namespace)| *namespace == attribute_name.ns);
+            if !in_scope {
+                self.write(b" xmlns:")?;
+                self.write(prefix.as_bytes())?;
+                self.write(b"=\"")?;
+                self.write_escaped(&attribute_name.ns, true)?;
+                self.write(b"\"")?;
+                prefixes.push((prefix.into(), attribute_name.ns.clone()));
+            }
+        }
+        // the outermost element of each namespace says which it is, as XML needs
+        let parent_namespace = self.parent().name.as_ref().map(|parent| &parent.ns);
+        let declared_namespaces: [Namespace; 3] = [ns!(html), ns!(svg), ns!(mathml)];
+        if xhtml
+            && !declares_namespace
+            && parent_namespace != Some(&name.ns)
+            && declared_namespaces.contains(&name.ns)
+        {
+            self.write(b" xmlns=\"")?;
+            self.write(name.ns.as_bytes())?;
+            self.write(b"\"")?;
+        }
+        let void = name.ns == ns!(html) && VOID_ELEMENTS.contains(&name.local);
+        self.write(if void && xhtml { b" />" } else { b">" })?;
+        self.stack.push(ElementInfo {
+            name: Some(name),
+            ignore_children: void,
+            block_children: false,
+            prefixes,
+        });
+        Ok(())
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        let info = self.stack.pop().unwrap_or_default();
+        if info.ignore_children {
+            return Ok(());
+        }
+        if info.block_children {
+            self.new_line(self.stack.len() - 1)?;
+        } else {
+            self.flush_whitespace()?;
+        }
+        self.write(b"</")?;
+        self.write(name.local.as_bytes())?;
+        self.write(b">")
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        if self.options.pretty
+            && text.chars().all(|c| c.is_ascii_whitespace())
+            && !self.preformatted()
+        {
+            self.pending_whitespace.push_str(text);
+            return Ok(());
+        }
+        self.flush_whitespace()?;
+        if self.options.format == MarkupFormat::Html && self.in_html(&RAW_TEXT_ELEMENTS) {
+            self.write(text.as_bytes())
+        } else {
+            self.write_escaped(text, false)
+        }
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        self.flush_whitespace()?;
+        self.write(b"<!--")?;
+        if self.options.format == MarkupFormat::Xhtml {
+            // XML comments may not contain `--` or end with `-`
+            let mut comment = String::with_capacity(text.len());
+            for c in text.chars() {
+                if c == '-' && comment.ends_with('-') {
+                    comment.push(' ');
+                }
+                comment.push(c);
+            }
+            if comment.ends_with('-') {
+                comment.push(' ');
+            }
+            self.write(comment.as_bytes())?;
+        } else {
+            self.write(text.as_bytes())?;
+        }
+        self.write(b"-->")
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        self.flush_whitespace()?;
+        self.write(b"<!DOCTYPE ")?;
+        self.write(name.as_bytes())?;
+        self.write(b">")
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        self.flush_whitespace()?;
+        self.write(b"<?")?;
+        self.write(target.as_bytes())?;
+        self.write(b" ")?;
+        self.write(data.as_bytes())?;
+        self.write(b">")
+    }
+}
@@ -0,0 +1,174 @@
+use super::{
+    dom::{Handle, NodeData},
+    matcher::Matcher,
+    runs::{run_length, TextRun},
+    Builder, Html,
+};
+use html5ever::{local_name, LocalName};
+
+/// Elements which start a new line of text, so that words either side of them are not run
+/// together in snippets.
+const BLOCK_ELEMENTS: [LocalName; 29] = [
+    local_name!("address"),
+    local_name!("article"),
+    local_name!("aside"),
+    local_name!("blockquote"),
+    local_name!("br"),
+    local_name!("dd"),
+    local_name!("div"),
+    local_name!("dl"),
+    local_name!("dt"),
+    local_name!("figcaption"),
+    local_name!("figure"),
+    local_name!("footer"),
+    local_name!("h1"),
+    local_name!("h2"),
+    local_name!("h3"),
+    local_name!("h4"),
+    local_name!("h5"),
+    local_name!("h6"),
+    local_name!("header"),
+    local_name!("hr"),
+    local_name!("li"),
+    local_name!("main"),
+    local_name!("nav"),
+    local_name!("p"),
+    local_name!("pre"),
+    local_name!("section"),
+    local_name!("td"),
+    local_name!("th"),
+    local_name!("tr"),
+];
+
+const ELLIPSIS: &str = "…";
+
+enum Visit {
+    Node(Handle),
+    // a run of text split over sibling nodes, as `run_length` finds one
+    Text(Vec<Handle>),
+    BlockEnd,
+}
+
+impl<'a> Builder<'a> {
+    /// Parses `src` as an HTML fragment and returns plain-text excerpts around each match of the
+    /// search term, with up to `context` characters of surrounding text either side. Excerpts
+    /// which would overlap are merged, and runs of whitespace are collapsed to a single space.
+    pub fn snippets(&self, src: &str, context: usize) -> Vec<String> {
+        self.snippets_dom(Html::parse(src), context)
+    }
+
+    /// Like [`Builder::snippets`], for already-parsed HTML.
+    pub fn snippets_dom(&self, html: Html, context: usize) -> Vec<String> {
+        let dom = html.0;
+        self.strip_highlights(&dom.document);
+        let Some(matcher) = self.matcher() else {
+            return Vec::new();
+        };
+        let (text, matches) = self.text_content(&dom.root(), &matcher);
+        excerpts(&text, &matches, context)
+    }
+
+    /*
+     * Collects the searchable text below `root`, with a newline either side of block elements,
+     * together with the byte ranges of matches within it.
+     */
+    fn text_content(&self, root: &Handle, matcher: &Matcher) -> (String, Vec<(usize, usize)>) {
+        let mut text = String::new();
+        let mut matches = Vec::new();
+        let mut stack: Vec<Visit> = child_visits(root);
+        while let Some(visit) = stack.pop() {
+            let node = match visit {
+                Visit::Node(node) => node,
+                Visit::Text(nodes) => {
+                    let run = TextRun::new(&nodes);
+                    matches.extend(matcher.find(&run.text).into_iter().map(|search_match| {
+                        (
+                            text.len() + search_match.start,
+                            text.len() + search_match.end,
+                        )
+                    }));
+                    text.push_str(&run.text);
+                    continue;
+                }
+                Visit::BlockEnd => {
+                    text.push('\n');
+                    continue;
+                }
+            };
+            if let NodeData::Element { ref name, .. } = node.data {
+                if !self.search_children(&node) {
+                    continue;
+                }
+                if BLOCK_ELEMENTS.contains(&name.local) {
+                    text.push('\n');
+                    stack.push(Visit::BlockEnd);
+                }
+                stack.extend(child_visits(&node));
+            }
+        }
+        (text, matches)
+    }
+}
+
+/*
+ * The children of `node`, in reverse order for popping off a stack, with each run of text as one
+ * visit.
+ */
+fn child_visits(node: &Handle) -> Vec<Visit> {
+    let children = node.children.borrow();
+    let mut visits = Vec::new();
+    let mut index = 0;
+    while index < children.len() {
+        let length = run_length(&children[index..]);
+        if length == 0 {
+            visits.push(Visit::Node(children[index].clone()));
+            index += 1;
+        } else {
+            visits.push(Visit::Text(children[index..index + length].to_vec()));
+            index += length;
+        }
+    }
+    visits.reverse();
+    visits
+}
+
+fn excerpts(text: &str, matches: &[(usize, usize)], context: usize) -> Vec<String> {
+    let mut windows: Vec<(usize, usize)> = Vec::new();
+    for &(start, end) in matches {
+        let window_start = text[..start]
+            .char_indices()
+            .rev()
+            .take(context)
+            .last()
+            .map_or(start, |(index, _)| index);
+        let window_end = text[end..]
+            .char_indices()
+            .nth(context)
+            .map_or(text.len(), |(index, _)| end + index);
+        match windows.last_mut() {
+            Some((_, last_end)) if window_start <= *last_end => {
+                *last_end = window_end.max(*last_end);
+            }
+            _ => windows.push((window_start, window_end)),
+        }
+    }
+    windows
+        .into_iter()
+        .map(|(start, end)| {
+            let mut excerpt = String::new();
+            if !text[..start].trim().is_empty() {
+                excerpt.push_str(ELLIPSIS);
+            }
+            excerpt.push_str(
+                &text[start..end]
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+            if !text[end..].trim().is_empty() {
+                excerpt.push_str(ELLIPSIS);
+            }
+            excerpt
+        })
+        .collect()
+}
@@ -0,0 +1,449 @@
+use super::{
+    dom::{Handle, Node, NodeData, RcDom},
+    folding::FoldedText,
+    position::Offset,
+};
+use html5ever::data::{C1_REPLACEMENTS, NAMED_ENTITIES};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::{collections::HashMap, ops::Range, rc::Rc};
+#[cfg(feature = "wasm")]
+use tsify::Tsify;
+
+/// Elements whose content is raw text: markup and character references in it are not parsed.
+const RAW_TEXT_ELEMENTS: [&str; 7] = [
+    "iframe",
+    "noembed",
+    "noframes",
+    "plaintext",
+    "script",
+    "style",
+    "xmp",
+];
+
+/// Elements whose content is text with character references, but no markup.
+const ESCAPABLE_RAW_TEXT_ELEMENTS: [&str; 2] = ["textarea", "title"];
+
+/// Text nodes are looked for at most this many characters of text past where the previous one
+/// was found, so text the parser moved (such as text misplaced inside a table) cannot send the
+/// search to the end of the input for every node after it.
+const ALIGNMENT_LOOKAHEAD: usize = 1024;
+
+/// A position in the original input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "camelCase"))]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct SourcePosition {
+    /// Offset from the start of the input.
+    pub offset: Offset,
+    /// Line number, starting from 1.
+    pub line: usize,
+    /// Offset from the start of the line.
+    pub column: Offset,
+}
+
+/// The part of the original input a match was parsed from, which may be longer than the match
+/// itself: `&amp;` in the input is a single `&` in the text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "camelCase"))]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct SourceSpan {
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+}
+
+/// A character of text content in the input, with the bytes it was written as.
+struct SourceChar {
+    c: char,
+    start: usize,
+    end: usize,
+}
+
+/*
+ * Maps text nodes in a DOM parsed from `src` back onto `src`. The parser does not record where
+ * tokens came from, so `src` is scanned again for its text, which is lined up with the text
+ * nodes in document order. Text the parser moved out of order, which it records in the DOM, is
+ * left out.
+ */
+pub(crate) struct SourceMap<'s> {
+    src: &'s str,
+    // byte offset and `Offset` of the start of each line
+    lines: Vec<(usize, Offset)>,
+    nodes: HashMap<*const Node, FoldedText>,
+}
+
+impl<'s> SourceMap<'s> {
+    pub fn new(src: &'s str, dom: &RcDom) -> Self {
+        let mut lines = vec![(0, Offset::default())];
+        let mut offset = Offset::default();
+        let mut line_start = 0;
+        for (index, _) in src.match_indices('\n') {
+            offset = offset.advance(&src[line_start..=index]);
+            line_start = index + 1;
+            lines.push((line_start, offset));
+        }
+
+        let text = source_text(src);
+        let mut nodes = HashMap::new();
+        let mut cursor = 0;
+        let mut stack: Vec<Handle> = vec![dom.document.clone()];
+        while let Some(node) = stack.pop() {
+            // moved text is left without a source, rather than being looked for out of order
+            if let (NodeData::Text { ref contents }, false) =
+                (&node.data, dom.moved_text.contains(&Rc::as_ptr(&node)))
+            {
+                let contents = contents.borrow();
+                if let Some(start) = find_text(&text, cursor, &contents) {
+                    let mut folded = FoldedText::with_capacity(contents.len());
+                    for (c, source) in contents.chars().zip(&text[start..]) {
+                        folded.push(c.encode_utf8(&mut [0; 4]), source.start..source.end);
+                    }
+                    cursor = start + contents.chars().count();
+                    nodes.insert(Rc::as_ptr(&node), folded);
+                }
+            }
+            stack.extend(node.children.borrow().iter().rev().cloned());
+            if let NodeData::Element {
+                template_contents: ref contents,
+                ..
+            } = node.data
+            {
+                stack.extend(contents.borrow().iter().cloned());
+            }
+        }
+        SourceMap { src, lines, nodes }
+    }
+
+    /// Where bytes `start..end` of the contents of text node `node` came from in the input, if
+    /// the node could be found there.
+    pub fn span(&self, node: &Handle, start: usize, end: usize) -> Option<SourceSpan> {
+        let source = self.nodes.get(&Rc::as_ptr(node))?.source(start, end);
+        Some(SourceSpan {
+            start: self.position(source.start),
+            end: self.position(source.end),
+        })
+    }
+
+    fn position(&self, byte: usize) -> SourcePosition {
+        let line = self.lines.partition_point(|&(start, _)| start <= byte);
+        let (line_start, line_offset) = self.lines[line - 1];
+        let column = Offset::default().advance(&self.src[line_start..byte]);
+        SourcePosition {
+            offset: Offset {
+                bytes: line_offset.bytes + column.bytes,
+                utf16: line_offset.utf16 + column.utf16,
+                chars: line_offset.chars + column.chars,
+            },
+            line,
+            column,
+        }
+    }
+}
+
+/*
+ * Index in `text` at or after `cursor` where the characters of `contents` appear in a row.
+ */
+fn find_text(text: &[SourceChar], cursor: usize, contents: &str) -> Option<usize> {
+    let first = contents.chars().next()?;
+    let length = contents.chars().count();
+    let last_start = text
+        .len()
+        .checked_sub(length)?
+        .min(cursor + ALIGNMENT_LOOKAHEAD);
+    (cursor..=last_start).find(|&start| {
+        text[start].c == first
+            && text[start..start + length]
+                .iter()
+                .zip(contents.chars())
+                .all(|(source, c)| source.c == c)
+    })
+}
+
+/// A start or end tag in the input.
+pub(crate) struct SourceTag {
+    /// The tag name, lowercased.
+    pub name: String,
+    pub end_tag: bool,
+    /// Attribute names, lowercased, with their values decoded.
+    pub attributes: Vec<(String, String)>,
+    /// Where the tag is in the input, from `<` to `>`.
+    pub span: Range<usize>,
+}
+
+/*
+ * The start and end tags in `src`, outside comments and raw text, in order.
+ */
+pub(crate) fn source_tags(src: &str) -> Vec<SourceTag> {
+    let mut tags = Vec::new();
+    scan(src, |span, end_tag| {
+        let tag = &src[span.clone()];
+        let name_start = if end_tag { 2 } else { 1 };
+        let name_end = tag[name_start..]
+            .find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+            .map_or(tag.len(), |length| name_start + length);
+        tags.push(SourceTag {
+            name: tag[name_start..name_end].to_ascii_lowercase(),
+            end_tag,
+            attributes: if end_tag {
+                Vec::new()
+            } else {
+                attributes(&tag[name_end..])
+            },
+            span,
+        });
+    });
+    tags
+}
+
+/*
+ * The text content of `src`, outside tags, comments and doctypes, with character references
+ * decoded and line breaks normalised as the tokeniser does.
+ */
+fn source_text(src: &str) -> Vec<SourceChar> {
+    scan(src, |_, _| {})
+}
+
+/*
+ * Scans `src` as `source_text` describes, passing the span of each start or end tag, and
+ * whether it is an end tag, to `on_tag`.
+ */
+fn scan(src: &str, mut on_tag: impl FnMut(Range<usize>, bool)) -> Vec<SourceChar> {
+    let bytes = src.as_bytes();
+    let mut text = Vec::with_capacity(src.len());
+    let mut index = 0;
+    // how many SVG and MathML elements the scan is inside, where CDATA sections are text and
+    // no element's content is raw text
+    let mut foreign_depth: usize = 0;
+    while index < src.len() {
+        let rest = &src[index..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            index = comment
+                .find("-->")
+                .map_or(src.len(), |end| index + 4 + end + 3);
+        } else if let Some(cdata) = rest.strip_prefix("<![CDATA[").filter(|_| foreign_depth > 0) {
+            let content_start = index + "<![CDATA[".len();
+            let content_end = cdata
+                .find("]]>")
+                .map_or(src.len(), |end| content_start + end);
+            push_text(src, content_start, content_end, false, &mut text);
+            index = (content_end + "]]>".len()).min(src.len());
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            index = rest.find('>').map_or(src.len(), |end| index + end + 1);
+        } else if rest.starts_with("</")
+            && bytes.get(index + 2).is_some_and(u8::is_ascii_alphabetic)
+        {
+            let end = tag_end(src, index);
+            if is_foreign_root(&src[index + 2..end]) {
+                foreign_depth = foreign_depth.saturating_sub(1);
+            }
+            on_tag(index..end, true);
+            index = end;
+        } else if rest.starts_with('<') && bytes.get(index + 1).is_some_and(u8::is_ascii_alphabetic)
+        {
+            let name_end = rest[1..]
+                .find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+                .map_or(src.len(), |end| index + 1 + end);
+            let name = src[index + 1..name_end].to_ascii_lowercase();
+            let end = tag_end(src, index);
+            if is_foreign_root(&name) && !src[index..end].ends_with("/>") {
+                foreign_depth += 1;
+            }
+            on_tag(index..end, false);
+            index = end;
+            let raw = RAW_TEXT_ELEMENTS.contains(&name.as_str());
+            if foreign_depth == 0 && (raw || ESCAPABLE_RAW_TEXT_ELEMENTS.contains(&name.as_str())) {
+                let content_end = if name == "plaintext" {
+                    src.len()
+                } else {
+                    find_end_tag(src, index, &name)
+                };
+                push_text(src, index, content_end, !raw, &mut text);
+                index = content_end;
+            }
+        } else {
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let next_tag = rest[first..]
+                .find('<')
+                .map_or(src.len(), |end| index + first + end);
+            push_text(src, index, next_tag, true, &mut text);
+            index = next_tag;
+        }
+    }
+    text
+}
+
+/*
+ * Whether the tag name at the start of `name` is `svg` or `math`, whose content is foreign.
+ */
+fn is_foreign_root(name: &str) -> bool {
+    let length = name
+        .find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+        .unwrap_or(name.len());
+    name[..length].eq_ignore_ascii_case("svg") || name[..length].eq_ignore_ascii_case("math")
+}
+
+/*
+ * The attributes written in `rest`, the part of a start tag after its name.
+ */
+fn attributes(rest: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = rest;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        if rest.is_empty() || rest.starts_with('>') {
+            return attributes;
+        }
+        // the first character is part of the name even if it is `=`, and may be any character
+        let first = rest.chars().next().map_or(0, char::len_utf8);
+        let name_length = rest[first..]
+            .find(|c: char| c.is_ascii_whitespace() || matches!(c, '/' | '>' | '='))
+            .map_or(rest.len(), |length| length + first);
+        let name = rest[..name_length].to_ascii_lowercase();
+        rest = rest[name_length..].trim_start_matches(|c: char| c.is_ascii_whitespace());
+        let mut value = String::new();
+        if let Some(after_equals) = rest.strip_prefix('=') {
+            rest = after_equals.trim_start_matches(|c: char| c.is_ascii_whitespace());
+            let (raw, after_value) = match rest.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let length = rest[1..].find(quote).unwrap_or(rest.len() - 1);
+                    (&rest[1..1 + length], rest.get(length + 2..).unwrap_or(""))
+                }
+                _ => {
+                    let length = rest
+                        .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                        .unwrap_or(rest.len());
+                    rest.split_at(length)
+                }
+            };
+            let mut decoded = Vec::new();
+            push_text(raw, 0, raw.len(), true, &mut decoded);
+            value = decoded.into_iter().map(|source| source.c).collect();
+            rest = after_value;
+        }
+        attributes.push((name, value));
+    }
+}
+
+/*
+ * Index just past the `>` closing the tag starting at `start`, skipping quoted attribute values.
+ */
+fn tag_end(src: &str, start: usize) -> usize {
+    let mut quote = None;
+    for (index, c) in src[start..].char_indices() {
+        match (quote, c) {
+            (Some(open), _) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return start + index + 1,
+            _ => {}
+        }
+    }
+    src.len()
+}
+
+/*
+ * Index of the end tag closing raw text element `name` at or after `start`.
+ */
+fn find_end_tag(src: &str, start: usize, name: &str) -> usize {
+    let mut from = start;
+    while let Some(found) = src[from..].find("</") {
+        let tag = from + found;
+        let name_end = tag + 2 + name.len();
+        if src
+            .get(tag + 2..name_end)
+            .is_some_and(|candidate| candidate.eq_ignore_ascii_case(name))
+            && src[name_end..]
+                .chars()
+                .next()
+                .is_none_or(|c| c.is_ascii_whitespace() || c == '/' || c == '>')
+        {
+            return tag;
+        }
+        from = tag + 2;
+    }
+    src.len()
+}
+
+/*
+ * Appends the characters of `src[start..end]` to `text`, decoding character references if
+ * `decode` is set.
+ */
+fn push_text(src: &str, start: usize, end: usize, decode: bool, text: &mut Vec<SourceChar>) {
+    let mut index = start;
+    while index < end {
+        let rest = &src[index..end];
+        if decode && rest.starts_with('&') {
+            if let Some((chars, length)) = character_reference(rest) {
+                for c in chars.into_iter().flatten() {
+                    text.push(SourceChar {
+                        c,
+                        start: index,
+                        end: index + length,
+                    });
+                }
+                index += length;
+                continue;
+            }
+        }
+        let c = rest.chars().next().expect("`index` is before `end`");
+        let length = if rest.starts_with("\r\n") {
+            2
+        } else {
+            c.len_utf8()
+        };
+        text.push(SourceChar {
+            c: if c == '\r' { '\n' } else { c },
+            start: index,
+            end: index + length,
+        });
+        index += length;
+    }
+}
+
+/*
+ * Decodes the character reference `rest` starts with, returning the characters it stands for
+ * and its length in bytes.
+ */
+fn character_reference(rest: &str) -> Option<([Option<char>; 2], usize)> {
+    if let Some(numeric) = rest.strip_prefix("&#") {
+        let (digits, radix, prefix) = match numeric.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16, 3),
+            None => (numeric, 10, 2),
+        };
+        let length = digits
+            .find(|c: char| !c.is_digit(radix))
+            .unwrap_or(digits.len());
+        if length == 0 {
+            return None;
+        }
+        let value = u32::from_str_radix(&digits[..length], radix).unwrap_or(u32::MAX);
+        let c = match value {
+            0x80..=0x9F => C1_REPLACEMENTS[(value - 0x80) as usize],
+            _ => None,
+        }
+        .or_else(|| char::from_u32(value).filter(|&c| c != '\0'))
+        .unwrap_or('\u{FFFD}');
+        let semicolon = usize::from(digits[length..].starts_with(';'));
+        return Some(([Some(c), None], prefix + length + semicolon));
+    }
+    let name_length = rest[1..]
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .map_or(rest.len() - 1, |length| {
+            length + usize::from(rest[1 + length..].starts_with(';'))
+        });
+    // the longest name which is an entity, since some (such as `&amp`) need no semicolon. The
+    // table also maps every prefix of a name to (0, 0), so that `&A` is not an entity; names
+    // without a semicolon only have a value if they are one of the legacy entities.
+    (1..=name_length).rev().find_map(|length| {
+        let &(first, second) = NAMED_ENTITIES
+            .get(&rest[1..1 + length])
+            .filter(|&&(first, _)| first != 0)?;
+        Some((
+            [
+                char::from_u32(first),
+                char::from_u32(second).filter(|_| second != 0),
+            ],
+            1 + length,
+        ))
+    })
+}
@@ -0,0 +1,215 @@
+use super::{
+    dom::{Handle, NodeData, RcDom, SerializableHandle},
+    source::{source_tags, SourceMap},
+    Builder, Diagnostics, MatchStatistics,
+};
+use html5ever::{
+    driver,
+    serialize::{serialize, SerializeOpts, TraversalScope},
+    tendril::TendrilSink,
+};
+use std::{cmp::Reverse, collections::HashMap};
+
+/// HTML highlighted by [`Builder::splice`]: the input with highlight elements inserted and every
+/// other byte left as it was.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SplicedHtml {
+    pub html: String,
+    pub statistics: MatchStatistics,
+    /// Parse errors and quirks mode html5ever reported for the input.
+    pub diagnostics: Diagnostics,
+}
+
+impl<'a> Builder<'a> {
+    /// Highlights the search term in the HTML fragment `src` by inserting highlight elements into
+    /// the text of `src` itself, rather than serialising the parsed DOM, so attribute quoting,
+    /// character references, whitespace and everything else outside the highlights is kept
+    /// byte for byte.
+    ///
+    /// Matches are found as [`Builder::ranges`] finds them, so attribute matches are neither
+    /// annotated nor counted. Matches whose text cannot be found in `src`, or which are written
+    /// with markup the parser ignored in the middle, are counted but not highlighted. The tags of
+    /// highlights left over from an earlier run are removed first, so splicing again does not
+    /// nest them.
+    pub fn splice(&self, src: &str) -> SplicedHtml {
+        self.splice_source(src, Self::make_parser)
+    }
+
+    /// Like [`Builder::splice`], parsing `src` as a full document.
+    pub fn splice_document(&self, src: &str) -> SplicedHtml {
+        self.splice_source(src, Self::make_document_parser)
+    }
+
+    fn splice_source(&self, src: &str, parser: fn() -> driver::Parser<RcDom>) -> SplicedHtml {
+        let dom = parser().one(src);
+        if self.has_highlights(&dom.document) {
+            // take out the tags of earlier highlights, so highlighting again does not nest them
+            let src = self.remove_highlight_tags(src);
+            return self.splice_dom(&src, parser().one(src.as_str()));
+        }
+        self.splice_dom(src, dom)
+    }
+
+    /*
+     * Whether there is a highlight element anywhere below `root`.
+     */
+    fn has_highlights(&self, root: &Handle) -> bool {
+        let mut stack = vec![root.clone()];
+        while let Some(node) = stack.pop() {
+            if self.is_highlight(&node) {
+                return true;
+            }
+            stack.extend(node.children.borrow().iter().cloned());
+            if let NodeData::Element {
+                template_contents: ref contents,
+                ..
+            } = node.data
+            {
+                stack.extend(contents.borrow().iter().cloned());
+            }
+        }
+        false
+    }
+
+    /*
+     * `src` without the start and end tags of highlight elements, keeping their contents.
+     */
+    fn remove_highlight_tags(&self, src: &str) -> String {
+        let (marker_name, marker_value) = self.marker_attribute;
+        // for each tag name, whether each element of that name still open is a highlight
+        let mut open: HashMap<String, Vec<bool>> = HashMap::new();
+        let mut html = String::with_capacity(src.len());
+        let mut copied = 0;
+        for tag in source_tags(src) {
+            let elements = open.entry(tag.name).or_default();
+            let is_highlight = if tag.end_tag {
+                elements.pop().unwrap_or(false)
+            } else {
+                let is_highlight = tag
+                    .attributes
+                    .iter()
+                    .any(|(name, value)| name == marker_name && value == marker_value);
+                elements.push(is_highlight);
+                is_highlight
+            };
+            if is_highlight {
+                html.push_str(&src[copied..tag.span.start]);
+                copied = tag.span.end;
+            }
+        }
+        html.push_str(&src[copied..]);
+        html
+    }
+
+    fn splice_dom(&self, src: &str, dom: RcDom) -> SplicedHtml {
+        let (parts, mut statistics) = self.find_range_parts(&dom, Some(&SourceMap::new(src, &dom)));
+        statistics.highlighted = 0;
+        for term in statistics.terms.iter_mut() {
+            term.highlighted = 0;
+        }
+        let terms = self.terms();
+
+        // each highlight is an opening tag at the start of its span and a closing tag at the end
+        let mut insertions = Vec::new();
+        // whether a part of the match the current range is part of has been highlighted
+        let mut counted = false;
+        for (range, continued) in parts {
+            if !continued {
+                counted = false;
+            }
+            let Some(span) = range.source else {
+                continue;
+            };
+            let (start, end) = (span.start.offset.bytes, span.end.offset.bytes);
+            if has_markup(&src[start..end]) {
+                continue;
+            }
+            let element =
+                self.highlight_element(!counted && statistics.highlighted == 0, &terms[range.term]);
+            let (open, close) = element_tags(&element.into(), self.highlight_tag);
+            if !counted {
+                statistics.record_highlight(range.term);
+                counted = true;
+            }
+            insertions.push(Insertion {
+                at: start,
+                close: false,
+                other_end: end,
+                text: open,
+            });
+            insertions.push(Insertion {
+                at: end,
+                close: true,
+                other_end: start,
+                text: close,
+            });
+        }
+        // highlights are disjoint or nested: at the same offset, highlights end before others
+        // start, outer highlights open before inner ones, and inner ones close before outer ones
+        insertions.sort_by_key(|insertion| {
+            (insertion.at, !insertion.close, Reverse(insertion.other_end))
+        });
+
+        let mut html = String::with_capacity(
+            src.len()
+                + insertions
+                    .iter()
+                    .map(|insertion| insertion.text.len())
+                    .sum::<usize>(),
+        );
+        let mut copied = 0;
+        for insertion in insertions {
+            html.push_str(&src[copied..insertion.at]);
+            html.push_str(&insertion.text);
+            copied = insertion.at;
+        }
+        html.push_str(&src[copied..]);
+        SplicedHtml {
+            html,
+            statistics,
+            diagnostics: Diagnostics::from(&dom),
+        }
+    }
+}
+
+/// A tag to insert into the source at byte offset `at`.
+struct Insertion {
+    at: usize,
+    close: bool,
+    // offset of the matching tag
+    other_end: usize,
+    text: String,
+}
+
+/*
+ * Whether `source` has a tag, comment or doctype in it.
+ */
+fn has_markup(source: &str) -> bool {
+    source.match_indices('<').any(|(index, _)| {
+        source[index + 1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '/' | '!' | '?'))
+    })
+}
+
+/*
+ * The opening and closing tags of the empty element `element`, as the serialiser writes them.
+ */
+fn element_tags(element: &SerializableHandle, tag: &str) -> (String, String) {
+    let mut serialized = Vec::new();
+    let opts = SerializeOpts {
+        traversal_scope: TraversalScope::IncludeNode,
+        ..SerializeOpts::default()
+    };
+    serialize(&mut serialized, element, opts)
+        .expect("Writing to a string shouldn't fail (expect on OOM)");
+    let mut open = String::from_utf8(serialized).expect("html5ever only supports UTF8");
+    let close = format!("</{tag}>");
+    if open.ends_with(&close) {
+        open.truncate(open.len() - close.len());
+        (open, close)
+    } else {
+        (open, String::new())
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "wasm")]
+use tsify::Tsify;
+
+/// Number of matches found for a single search term.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct TermStatistics {
+    pub term: String,
+    pub count: usize,
+    /// Matches wrapped in a highlight element, which is fewer than `count` when a highlight
+    /// limit was reached.
+    pub highlighted: usize,
+}
+
+/// Match counts from highlighting a [`super::Document`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "camelCase"))]
+#[cfg_attr(feature = "wasm", derive(Tsify))]
+pub struct MatchStatistics {
+    /// Matches found in text content, across all terms.
+    pub total: usize,
+    /// Matches wrapped in a highlight element, across all terms. Fewer than `total` when a
+    /// highlight limit was reached.
+    pub highlighted: usize,
+    /// Matches per term, in the order the terms appear in the query.
+    pub terms: Vec<TermStatistics>,
+    /// Elements annotated because one of their searched attributes matched.
+    pub attribute_matches: usize,
+    /// Whether the node or time budget ran out before the whole document was searched, in
+    /// which case the counts only cover the part searched.
+    pub budget_exhausted: bool,
+}
+
+impl MatchStatistics {
+    pub(crate) fn new<'t>(terms: impl IntoIterator<Item = &'t str>) -> Self {
+        MatchStatistics {
+            total: 0,
+            highlighted: 0,
+            terms: terms
+                .into_iter()
+                .map(|term| TermStatistics {
+                    term: term.to_string(),
+                    count: 0,
+                    highlighted: 0,
+                })
+                .collect(),
+            attribute_matches: 0,
+            budget_exhausted: false,
+        }
+    }
+
+    pub(crate) fn record(&mut self, term_index: usize) {
+        self.total += 1;
+        self.terms[term_index].count += 1;
+    }
+
+    pub(crate) fn record_highlight(&mut self, term_index: usize) {
+        self.highlighted += 1;
+        self.terms[term_index].highlighted += 1;
+    }
+}
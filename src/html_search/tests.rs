@@ -1,4 +1,13 @@
-use crate::html_search::{search_html, Builder};
+use super::{
+    dom::{Handle, NodeData},
+    Document, Html,
+};
+use crate::html_search::{
+    diagnostics::QuirksMode, highlight_text, remove_highlights, search_html, Builder, EntityStyle,
+    HighlightRange, IncrementalSearch, MarkupFormat, MatchKind, Normalization, Offset, SearchTerm,
+    Segmentation, SerializationOptions, SerializationScope, TermStatistics,
+};
+use std::{collections::HashSet, rc::Rc};
 
 #[test]
 fn test_search() {
@@ -8,7 +17,7 @@ fn test_search() {
             r#"<h2>Heading</h2><p>Nobody likes maple in their apple flavoured Snapple. APPLE</p>"#,
         )
         .to_string();
-    let expected = r#"<h2>Heading</h2><p>Nobody likes maple in their <mark id="search-match">apple</mark> flavoured Sn<mark>apple</mark>. <mark>APPLE</mark></p>"#;
+    let expected = r#"<h2>Heading</h2><p>Nobody likes maple in their <mark id="search-match" data-searchlite="">apple</mark> flavoured Sn<mark data-searchlite="">apple</mark>. <mark data-searchlite="">APPLE</mark></p>"#;
     assert_eq!(result, expected);
 }
 
@@ -19,7 +28,7 @@ fn search_html_highlight_requested_term() {
         "apple",
     )
     .to_string();
-    let expected = r#"<h2>Heading</h2><p>Nobody likes maple in their <mark id="search-match">apple</mark> flavoured Sn<mark>apple</mark>. <mark>APPLE</mark></p><p>Paragraph with no matches</p><p>Paragraph which mentions <mark>apple</mark>s again</p>"#;
+    let expected = r#"<h2>Heading</h2><p>Nobody likes maple in their <mark id="search-match" data-searchlite="">apple</mark> flavoured Sn<mark data-searchlite="">apple</mark>. <mark data-searchlite="">APPLE</mark></p><p>Paragraph with no matches</p><p>Paragraph which mentions <mark data-searchlite="">apple</mark>s again</p>"#;
     assert_eq!(result, expected);
 }
 
@@ -43,6 +52,987 @@ fn search_html_highlight_highlights_nested_matches() {
     )
     .to_string();
 
-    let expected = r#"<h2>Heading</h2><p>Nobody likes maple in their <strong><mark id="search-match">apple</mark></strong> flavoured Sn<mark>apple</mark>. <mark>APPLE</mark></p><p>Paragraph with no matches</p><p>Paragraph which mentions <mark>apple</mark>s again</p>"#;
+    let expected = r#"<h2>Heading</h2><p>Nobody likes maple in their <strong><mark id="search-match" data-searchlite="">apple</mark></strong> flavoured Sn<mark data-searchlite="">apple</mark>. <mark data-searchlite="">APPLE</mark></p><p>Paragraph with no matches</p><p>Paragraph which mentions <mark data-searchlite="">apple</mark>s again</p>"#;
     assert_eq!(result, expected);
 }
+
+#[test]
+fn diagnostics_are_empty_for_well_formed_html() {
+    let diagnostics = Builder::new()
+        .search_term(Some("apple"))
+        .search(r#"<h2>Heading</h2><p>Nobody likes maple in their apple flavoured Snapple.</p>"#)
+        .diagnostics();
+    assert!(diagnostics.errors.is_empty());
+    assert_eq!(diagnostics.quirks_mode, QuirksMode::NoQuirks);
+}
+
+#[test]
+fn diagnostics_report_parse_errors_with_line() {
+    let diagnostics = Builder::new()
+        .search_term(Some("apple"))
+        .search("<p>First line</p>\n<p>Second line</div>\n<p>Third line &nosuch</p>")
+        .diagnostics();
+    let lines: Vec<u64> = diagnostics.errors.iter().map(|error| error.line).collect();
+    assert_eq!(lines, vec![2]);
+    assert!(!diagnostics.errors[0].message.is_empty());
+}
+
+#[test]
+fn search_html_is_idempotent_on_highlighted_input() {
+    let once = search_html(
+        r#"<h2>Heading</h2><p>Nobody likes maple in their <strong>apple</strong> flavoured Snapple. APPLE</p>"#,
+        "apple",
+    );
+    let twice = search_html(&once, "apple");
+    assert_eq!(twice, once);
+
+    let result = search_html(&once, "maple");
+    let expected = r#"<h2>Heading</h2><p>Nobody likes <mark id="search-match" data-searchlite="">maple</mark> in their <strong>apple</strong> flavoured Snapple. APPLE</p>"#;
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn search_keeps_author_marks() {
+    let result = Builder::new()
+        .search_term(Some("zzz"))
+        .search("<p>Keep <mark>this</mark> note</p>")
+        .to_string();
+    assert_eq!(result, "<p>Keep <mark>this</mark> note</p>");
+    assert_eq!(
+        remove_highlights(r#"<p><mark id="search-match">Keep</mark> this</p>"#),
+        r#"<p><mark id="search-match">Keep</mark> this</p>"#
+    );
+}
+
+#[test]
+fn remove_highlights_restores_original_html() {
+    let original = r#"<h2>Heading</h2><p>Nobody likes <mark class="author">maple</mark> in their <strong>apple</strong> flavoured Snapple. APPLE</p>"#;
+    let highlighted = search_html(original, "apple");
+    assert_eq!(remove_highlights(&highlighted), original);
+}
+
+#[test]
+fn unhighlight_only_removes_marks_with_marker_attribute() {
+    let highlighted = Builder::new()
+        .search_term(Some("apple"))
+        .marker_attribute(("data-highlight", "1"))
+        .search(r#"<p>Nobody likes <mark>maple</mark> in their apple flavoured Snapple.</p>"#)
+        .to_string();
+    let expected = r#"<p>Nobody likes <mark>maple</mark> in their <mark id="search-match" data-highlight="1">apple</mark> flavoured Sn<mark data-highlight="1">apple</mark>.</p>"#;
+    assert_eq!(highlighted, expected);
+
+    let result = Builder::new()
+        .marker_attribute(("data-highlight", "1"))
+        .unhighlight(&highlighted)
+        .to_string();
+    let expected = r#"<p>Nobody likes <mark>maple</mark> in their apple flavoured Snapple.</p>"#;
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn search_annotates_elements_with_matching_attributes() {
+    let result = Builder::new()
+        .search_term(Some("apple"))
+        .match_attributes(HashSet::from(["alt", "title"]))
+        .attribute_match_class(Some("attribute-match"))
+        .search(r#"<p><img src="apple.png" alt="An APPLE"><a href="/apple" title="Pears">Pears</a></p>"#)
+        .to_string();
+    let expected = r#"<p><img src="apple.png" alt="An APPLE" class="attribute-match" data-searchlite-attr-match="alt"><a href="/apple" title="Pears">Pears</a></p>"#;
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn unhighlight_removes_attribute_annotations() {
+    let original = r#"<p><img class="hero" alt="An apple" title="apple"> and an apple</p>"#;
+    let mut builder = Builder::new();
+    builder
+        .search_term(Some("apple"))
+        .match_attributes(HashSet::from(["alt", "title"]))
+        .attribute_match_class(Some("attribute-match"));
+    let highlighted = builder.search(original).to_string();
+    let expected = r#"<p><img class="hero attribute-match" alt="An apple" title="apple" data-searchlite-attr-match="alt title"> and an <mark id="search-match" data-searchlite="">apple</mark></p>"#;
+    assert_eq!(highlighted, expected);
+    assert_eq!(builder.search(&highlighted).to_string(), expected);
+    assert_eq!(builder.unhighlight(&highlighted).to_string(), original);
+}
+
+#[test]
+fn search_reports_match_statistics() {
+    let document = Builder::new()
+        .search_term(Some("apple pear"))
+        .match_attributes(HashSet::from(["alt"]))
+        .search(
+            r#"<p>Nobody likes maple in their apple flavoured Snapple. APPLE</p><img alt="Pear">"#,
+        );
+    let statistics = document.statistics();
+    assert_eq!(statistics.total, 3);
+    assert_eq!(
+        statistics.terms,
+        vec![
+            TermStatistics {
+                term: "apple".to_string(),
+                count: 3,
+                highlighted: 3
+            },
+            TermStatistics {
+                term: "pear".to_string(),
+                count: 0,
+                highlighted: 0
+            }
+        ]
+    );
+    assert_eq!(statistics.attribute_matches, 1);
+}
+
+#[test]
+fn search_does_not_highlight_inside_script_or_style() {
+    let result = search_html(
+        r#"<style>.apple { color: red; }</style><p class="apple">apple</p><script>let apple = 1;</script>"#,
+        "apple",
+    );
+    let expected = r#"<style>.apple { color: red; }</style><p class="apple"><mark id="search-match" data-searchlite="">apple</mark></p><script>let apple = 1;</script>"#;
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn search_document_keeps_document_structure() {
+    let result = Builder::new()
+        .search_term(Some("apple"))
+        .search_document(
+            r#"<!DOCTYPE html><html><head><title>Apple</title></head><body><p>An apple</p></body></html>"#,
+        )
+        .to_string();
+    let expected = r#"<!DOCTYPE html><html><head><title>Apple</title></head><body><p>An <mark id="search-match" data-searchlite="">apple</mark></p></body></html>"#;
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn snippets_return_context_around_matches() {
+    let snippets = Builder::new().search_term(Some("apple")).snippets(
+        r#"<h2>Heading</h2><p>Nobody likes maple in their apple flavoured Snapple.</p><p>Paragraph with no matches at all, then another apple at the end</p>"#,
+        12,
+    );
+    assert_eq!(
+        snippets,
+        vec![
+            "…le in their apple flavoured Snapple. Paragraph…".to_string(),
+            "…hen another apple at the end".to_string()
+        ]
+    );
+}
+
+#[test]
+fn search_uses_configured_highlight_element() {
+    let mut builder = Builder::new();
+    builder
+        .search_term(Some("apple"))
+        .highlight_tag("span")
+        .highlight_classes(vec!["highlight", "search"]);
+    let highlighted = builder
+        .search(r#"<p>An apple and an <span>apple</span></p>"#)
+        .to_string();
+    let expected = r#"<p>An <span id="search-match" class="highlight search" data-searchlite="">apple</span> and an <span><span class="highlight search" data-searchlite="">apple</span></span></p>"#;
+    assert_eq!(highlighted, expected);
+    assert_eq!(
+        builder.unhighlight(&highlighted).to_string(),
+        r#"<p>An apple and an <span>apple</span></p>"#
+    );
+}
+
+#[test]
+fn search_keeps_author_elements_of_the_highlight_tag() {
+    let src = r#"<p><span>Keep</span> this <span class="highlight">note</span> on pears</p>"#;
+    let mut builder = Builder::new();
+    builder
+        .search_term(Some("pear"))
+        .highlight_tag("span")
+        .highlight_classes(vec!["highlight"]);
+    let highlighted = builder.search(src).to_string();
+    let expected = r#"<p><span>Keep</span> this <span class="highlight">note</span> on <span id="search-match" class="highlight" data-searchlite="">pear</span>s</p>"#;
+    assert_eq!(highlighted, expected);
+    assert_eq!(builder.unhighlight(&highlighted).to_string(), src);
+    builder.search_term(Some("zzz"));
+    assert_eq!(builder.search(src).to_string(), src);
+}
+
+#[test]
+fn search_whole_word_case_sensitive() {
+    let result = Builder::new()
+        .search_term(Some("apple"))
+        .case_sensitive(true)
+        .whole_word(true)
+        .search(r#"<p>Nobody likes maple in their apple flavoured Snapple. APPLE</p>"#)
+        .to_string();
+    let expected = r#"<p>Nobody likes maple in their <mark id="search-match" data-searchlite="">apple</mark> flavoured Snapple. APPLE</p>"#;
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn search_skips_configured_tags() {
+    let result = Builder::new()
+        .search_term(Some("apple"))
+        .skip_tags(HashSet::from(["code"]))
+        .search(r#"<p>apple <code>apple</code></p>"#)
+        .to_string();
+    let expected =
+        r#"<p><mark id="search-match" data-searchlite="">apple</mark> <code>apple</code></p>"#;
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn search_ignores_repeated_and_non_space_whitespace_in_query() {
+    let document = Builder::new()
+        .search_term(Some("apple  \tpear\u{a0}plum"))
+        .search(r#"<p>An apple, a pear and a plum</p>"#);
+    let expected = r#"<p>An <mark id="search-match" data-searchlite="">apple</mark>, a <mark data-searchlite="">pear</mark> and a <mark data-searchlite="">plum</mark></p>"#;
+    assert_eq!(document.to_string(), expected);
+    assert_eq!(document.statistics().terms.len(), 3);
+}
+
+#[test]
+fn search_terms_apply_per_term_options() {
+    let result = Builder::new()
+        .search_terms(vec![
+            SearchTerm::new("new york").class("city"),
+            SearchTerm::new("Apple").case_sensitive(true),
+            SearchTerm::new("app").whole_word(true),
+        ])
+        .search(r#"<p>New York has an Apple store, an apple app and Snapple</p>"#)
+        .to_string();
+    let expected = r#"<p><mark id="search-match" class="city" data-searchlite="">New York</mark> has an <mark data-searchlite="">Apple</mark> store, an apple <mark data-searchlite="">app</mark> and Snapple</p>"#;
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn match_kind_resolves_overlapping_terms() {
+    let html = r#"<p>A new york pineapple</p>"#;
+    let highlight = |match_kind, terms: Vec<SearchTerm>| {
+        Builder::new()
+            .search_terms(terms)
+            .match_kind(match_kind)
+            .search(html)
+            .to_string()
+    };
+    let terms = || {
+        vec![
+            SearchTerm::new("new"),
+            SearchTerm::new("new york"),
+            SearchTerm::new("apple"),
+            SearchTerm::new("pineapple"),
+        ]
+    };
+
+    assert_eq!(
+        highlight(MatchKind::Standard, terms()),
+        r#"<p>A <mark id="search-match" data-searchlite="">new</mark> york <mark data-searchlite="">pineapple</mark></p>"#
+    );
+    assert_eq!(
+        highlight(MatchKind::LeftmostLongest, terms()),
+        r#"<p>A <mark id="search-match" data-searchlite="">new york</mark> <mark data-searchlite="">pineapple</mark></p>"#
+    );
+    assert_eq!(
+        highlight(
+            MatchKind::LeftmostFirst,
+            vec![
+                SearchTerm::new("new"),
+                SearchTerm::new("new york").priority(1),
+                SearchTerm::new("apple").priority(1),
+                SearchTerm::new("pineapple"),
+            ]
+        ),
+        r#"<p>A <mark id="search-match" data-searchlite="">new york</mark> <mark data-searchlite="">pineapple</mark></p>"#
+    );
+    assert_eq!(
+        highlight(MatchKind::LeftmostFirst, terms()),
+        r#"<p>A <mark id="search-match" data-searchlite="">new</mark> york <mark data-searchlite="">pineapple</mark></p>"#
+    );
+}
+
+#[test]
+fn match_kind_nested_highlights_inner_matches() {
+    let mut builder = Builder::new();
+    builder
+        .search_term(Some("apple pineapple york"))
+        .search_terms(vec![SearchTerm::new("new york")])
+        .match_kind(MatchKind::Nested);
+    let html = r#"<p>A new york pineapple</p>"#;
+    let document = builder.search(html);
+    let expected = r#"<p>A <mark id="search-match" data-searchlite="">new <mark data-searchlite="">york</mark></mark> <mark data-searchlite="">pine<mark data-searchlite="">apple</mark></mark></p>"#;
+    assert_eq!(document.to_string(), expected);
+    assert_eq!(document.statistics().total, 4);
+    assert_eq!(builder.unhighlight(&document.to_string()).to_string(), html);
+}
+
+#[test]
+fn search_stops_highlighting_at_limits() {
+    let document = Builder::new()
+        .search_term(Some("apple pear"))
+        .max_highlights(Some(3))
+        .max_highlights_per_term(Some(2))
+        .search("<p>apple apple apple pear</p><p>pear apple</p>");
+    assert_eq!(
+        document.to_string(),
+        r#"<p><mark id="search-match" data-searchlite="">apple</mark> <mark data-searchlite="">apple</mark> apple <mark data-searchlite="">pear</mark></p><p>pear apple</p>"#
+    );
+    let statistics = document.statistics();
+    assert_eq!(statistics.total, 6);
+    assert_eq!(statistics.highlighted, 3);
+    assert_eq!(statistics.terms[0].count, 4);
+    assert_eq!(statistics.terms[0].highlighted, 2);
+    assert!(!statistics.budget_exhausted);
+}
+
+#[test]
+fn search_leaves_rest_of_document_when_budget_runs_out() {
+    let document = Builder::new()
+        .search_term(Some("apple"))
+        .node_budget(Some(2))
+        .search("<p>apple</p><p>apple</p>");
+    assert_eq!(
+        document.to_string(),
+        r#"<p><mark id="search-match" data-searchlite="">apple</mark></p><p>apple</p>"#
+    );
+    assert_eq!(document.statistics().total, 1);
+    assert!(document.statistics().budget_exhausted);
+}
+
+#[test]
+fn search_cleans_up_query_terms() {
+    let mut builder = Builder::new();
+    builder
+        .search_term(Some("\"Apple,  a apple -- pear! plum"))
+        .min_term_length(2)
+        .trim_punctuation(true)
+        .deduplicate_terms(true)
+        .max_terms(Some(2));
+    assert_eq!(builder.effective_terms(), vec!["Apple", "pear"]);
+    assert_eq!(
+        builder
+            .search("<p>An apple, a pear and a plum</p>")
+            .to_string(),
+        r#"<p>An <mark id="search-match" data-searchlite="">apple</mark>, a <mark data-searchlite="">pear</mark> and a plum</p>"#
+    );
+}
+
+#[test]
+fn bigram_segmentation_splits_unspaced_query_terms() {
+    let mut builder = Builder::new();
+    builder
+        .search_term(Some("東京大学 iPhone手机"))
+        .segmentation(Segmentation::Bigram);
+    assert_eq!(
+        builder.effective_terms(),
+        vec!["東京", "京大", "大学", "iPhone", "手机"]
+    );
+    assert_eq!(
+        builder
+            .search("<p>東京大学の学生と京都大学、iPhone手机</p>")
+            .to_string(),
+        r#"<p><mark id="search-match" data-searchlite="">東京大学</mark>の学生と京都<mark data-searchlite="">大学</mark>、<mark data-searchlite="">iPhone</mark><mark data-searchlite="">手机</mark></p>"#
+    );
+}
+
+#[test]
+fn bigram_segmentation_treats_unspaced_characters_as_words() {
+    let result = Builder::new()
+        .search_term(Some("大学"))
+        .whole_word(true)
+        .segmentation(Segmentation::Bigram)
+        .search("<p>京都大学</p>")
+        .to_string();
+    assert_eq!(
+        result,
+        r#"<p>京都<mark id="search-match" data-searchlite="">大学</mark></p>"#
+    );
+}
+
+#[cfg(feature = "transliteration")]
+#[test]
+fn transliteration_matches_across_scripts() {
+    let result = Builder::new()
+        .search_term(Some("beograd tokyo kyouto"))
+        .transliterate(true)
+        .search("<p>Београд, とうきょう and キョウト. Tokyo</p>")
+        .to_string();
+    assert_eq!(
+        result,
+        r#"<p><mark id="search-match" data-searchlite="">Београд</mark>, とうきょう and <mark data-searchlite="">キョウト</mark>. <mark data-searchlite="">Tokyo</mark></p>"#
+    );
+}
+
+#[cfg(feature = "transliteration")]
+#[test]
+fn transliteration_highlights_whole_original_characters() {
+    let result = Builder::new()
+        .search_term(Some("Ljubljana"))
+        .transliterate(true)
+        .search("<p>Љубљана</p>")
+        .to_string();
+    assert_eq!(
+        result,
+        r#"<p><mark id="search-match" data-searchlite="">Љубљана</mark></p>"#
+    );
+    let result = Builder::new()
+        .search_term(Some("ubl"))
+        .transliterate(true)
+        .search("<p>Љубљана</p>")
+        .to_string();
+    assert_eq!(
+        result,
+        r#"<p>Љ<mark id="search-match" data-searchlite="">убљ</mark>ана</p>"#
+    );
+}
+
+#[cfg(feature = "transliteration")]
+#[test]
+fn transliteration_ignores_case_and_diacritics() {
+    for query in ["Šabac", "šabac", "sabac", "SABAC"] {
+        let result = Builder::new()
+            .search_term(Some(query))
+            .transliterate(true)
+            .search("<p>Шабац, Šabac</p>")
+            .to_string();
+        assert_eq!(
+            result,
+            r#"<p><mark id="search-match" data-searchlite="">Шабац</mark>, <mark data-searchlite="">Šabac</mark></p>"#,
+            "query {query:?}"
+        );
+    }
+    let result = Builder::new()
+        .search_term(Some("Đorđe"))
+        .transliterate(true)
+        .search("<p>Ђорђе, djordje</p>")
+        .to_string();
+    assert_eq!(
+        result,
+        r#"<p><mark id="search-match" data-searchlite="">Ђорђе</mark>, <mark data-searchlite="">djordje</mark></p>"#
+    );
+    let result = Builder::new()
+        .search_term(Some("Sabac"))
+        .transliterate(true)
+        .case_sensitive(true)
+        .search("<p>Шабац</p>")
+        .to_string();
+    assert_eq!(result, "<p>Шабац</p>");
+}
+
+#[test]
+fn normalization_matches_differently_encoded_text() {
+    let result = Builder::new()
+        .search_term(Some("caf\u{e9}"))
+        .normalization(Some(Normalization::Nfc))
+        .search("<p>cafe\u{301} au lait</p>")
+        .to_string();
+    assert_eq!(
+        result,
+        "<p><mark id=\"search-match\" data-searchlite=\"\">cafe\u{301}</mark> au lait</p>"
+    );
+    let result = Builder::new()
+        .search_term(Some("apple"))
+        .normalization(Some(Normalization::Nfkc))
+        .search("<p>ＡＰＰＬＥ pie</p>")
+        .to_string();
+    assert_eq!(
+        result,
+        r#"<p><mark id="search-match" data-searchlite="">ＡＰＰＬＥ</mark> pie</p>"#
+    );
+}
+
+#[test]
+fn search_skips_ignored_characters() {
+    let result = search_html("<p>A search&shy;able word</p>", "searchable");
+    assert_eq!(
+        result,
+        "<p>A <mark id=\"search-match\" data-searchlite=\"\">search\u{ad}able</mark> word</p>"
+    );
+    let result = Builder::new()
+        .search_term(Some("searchable"))
+        .ignored_characters(HashSet::new())
+        .search("<p>A search&shy;able word</p>")
+        .to_string();
+    assert_eq!(result, "<p>A search\u{ad}able word</p>");
+}
+
+#[test]
+fn phrases_match_across_any_whitespace() {
+    let result = Builder::new()
+        .search_terms(vec![SearchTerm::new("new york")])
+        .search("<p>New\n  York, new&nbsp;york and newyork</p>")
+        .to_string();
+    assert_eq!(
+        result,
+        "<p><mark id=\"search-match\" data-searchlite=\"\">New\n  York</mark>, <mark data-searchlite=\"\">new&nbsp;york</mark> and newyork</p>"
+    );
+}
+
+#[test]
+fn phrases_match_across_comments() {
+    let html = "<p>New<!-- line break -->\n york and <b>new</b> york</p>";
+    let highlighted =
+        "<p><mark id=\"search-match\" data-searchlite=\"\">New</mark><!-- line break -->\
+         <mark data-searchlite=\"\">\n york</mark> and <b>new</b> york</p>";
+    let mut builder = Builder::new();
+    builder.search_terms(vec![SearchTerm::new("new york")]);
+    let result = builder.search(html);
+    assert_eq!(result.to_string(), highlighted);
+    assert_eq!(result.statistics().total, 1);
+    assert_eq!(result.statistics().highlighted, 1);
+
+    let ranges = builder.ranges(html);
+    let parts: Vec<_> = ranges
+        .ranges
+        .iter()
+        .map(|range| {
+            let source = range.source.expect("every match has a source span");
+            (
+                range.path.clone(),
+                range.start.bytes,
+                range.end.bytes,
+                &html[source.start.offset.bytes..source.end.offset.bytes],
+            )
+        })
+        .collect();
+    assert_eq!(
+        parts,
+        vec![(vec![0, 0], 0, 3, "New"), (vec![0, 2], 0, 6, "\n york")]
+    );
+    assert_eq!(ranges.statistics.highlighted, 1);
+
+    let spliced = builder.splice(html);
+    assert_eq!(spliced.html, highlighted);
+    assert_eq!(spliced.statistics.highlighted, 1);
+
+    assert_eq!(builder.snippets(html, 0), vec!["New york…"]);
+}
+
+#[cfg(feature = "markdown")]
+#[test]
+fn search_markdown_skips_code_blocks_and_link_destinations() {
+    let markdown = "# Apple pie\n\nBake an [apple](https://example.com/apple) with `apple`.\n\n```\nlet apple = 1;\n```\n";
+    let mut builder = Builder::new();
+    builder.search_term(Some("apple"));
+    assert_eq!(
+        builder.search_markdown(markdown).to_string(),
+        "<h1><mark id=\"search-match\" data-searchlite=\"\">Apple</mark> pie</h1>\n<p>Bake an <a href=\"https://example.com/apple\"><mark data-searchlite=\"\">apple</mark></a> with <code><mark data-searchlite=\"\">apple</mark></code>.</p>\n<pre><code>let apple = 1;\n</code></pre>\n"
+    );
+    builder.markdown_code_blocks(true);
+    assert_eq!(builder.search_markdown(markdown).statistics().total, 4);
+}
+
+#[test]
+fn highlight_text_escapes_markup() {
+    let result = highlight_text("if a < b && apple > 0 { <b>apple</b> }", "apple");
+    assert_eq!(
+        result,
+        r#"if a &lt; b &amp;&amp; <mark id="search-match" data-searchlite="">apple</mark> &gt; 0 { &lt;b&gt;<mark data-searchlite="">apple</mark>&lt;/b&gt; }"#
+    );
+}
+
+#[test]
+fn ranges_locate_matches_without_changing_markup() {
+    let html = "<p>Ünïcode 🍎 apple</p>\n<ul><li>Apple <b>pie</b></li></ul>";
+    let mut builder = Builder::new();
+    builder.search_term(Some("apple pie"));
+    let ranges = builder.ranges_dom(&Html::parse(html));
+    let offset = |bytes, utf16, chars| Offset {
+        bytes,
+        utf16,
+        chars,
+    };
+    assert_eq!(
+        ranges.ranges,
+        vec![
+            HighlightRange {
+                path: vec![0, 0],
+                start: offset(15, 11, 10),
+                end: offset(20, 16, 15),
+                term: 0,
+                source: None
+            },
+            HighlightRange {
+                path: vec![2, 0, 0],
+                start: offset(0, 0, 0),
+                end: offset(5, 5, 5),
+                term: 0,
+                source: None
+            },
+            HighlightRange {
+                path: vec![2, 0, 1, 0],
+                start: offset(0, 0, 0),
+                end: offset(3, 3, 3),
+                term: 1,
+                source: None
+            },
+        ]
+    );
+    assert_eq!(ranges.statistics.total, 3);
+}
+
+#[test]
+fn ranges_give_source_spans() {
+    let html = "<p title=\"apple\">Tea &amp;\r\n<!-- apple -->apple</p>\n<script>apple()</script>\n<pre>\napple</pre>";
+    let mut builder = Builder::new();
+    builder.search_term(Some("& apple"));
+    let spans: Vec<_> = builder
+        .ranges(html)
+        .ranges
+        .iter()
+        .map(|range| {
+            let source = range.source.expect("every match has a source span");
+            let (start, end) = (source.start, source.end);
+            (
+                &html[start.offset.bytes..end.offset.bytes],
+                start.line,
+                start.column.chars,
+                end.line,
+            )
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        vec![("&amp;", 1, 21, 1), ("apple", 2, 14, 2), ("apple", 5, 0, 5)]
+    );
+}
+
+#[test]
+fn ranges_give_source_spans_after_bare_ampersands() {
+    let html = "<p>AT&T &notin; apple &ampx apple</p>";
+    let mut builder = Builder::new();
+    builder.search_term(Some("apple"));
+    let spans: Vec<_> = builder
+        .ranges(html)
+        .ranges
+        .iter()
+        .map(|range| {
+            let source = range.source.expect("every match has a source span");
+            (
+                &html[source.start.offset.bytes..source.end.offset.bytes],
+                source.start.offset.bytes,
+            )
+        })
+        .collect();
+    assert_eq!(spans, vec![("apple", 16), ("apple", 28)]);
+}
+
+/*
+ * For each range `term` has in `html`, the text it covers in its text node, and where its
+ * source span starts with the source it covers.
+ */
+fn source_spans<'h>(html: &'h str, term: &str) -> Vec<(String, Option<(usize, &'h str)>)> {
+    let dom = Html::parse(html);
+    let mut builder = Builder::new();
+    builder.search_term(Some(term));
+    builder
+        .ranges(html)
+        .ranges
+        .iter()
+        .map(|range| {
+            let mut node = dom.0.root();
+            for &index in &range.path {
+                let child = node.children.borrow()[index].clone();
+                node = child;
+            }
+            let NodeData::Text { ref contents } = node.data else {
+                panic!("a range's path leads to a text node");
+            };
+            let text = contents.borrow()[range.start.bytes..range.end.bytes].to_string();
+            let source = range.source.map(|source| {
+                let (start, end) = (source.start.offset.bytes, source.end.offset.bytes);
+                (start, &html[start..end])
+            });
+            (text, source)
+        })
+        .collect()
+}
+
+#[test]
+fn ranges_give_source_spans_around_moved_and_foreign_text() {
+    let owned = |spans: &[(&str, Option<(usize, &'static str)>)]| {
+        spans
+            .iter()
+            .map(|&(text, source)| (text.to_string(), source))
+            .collect::<Vec<_>>()
+    };
+    // text misplaced in a table is moved before it, and has no source, without throwing the
+    // text after it out
+    let html = "<table><tr><td>apple pie</td></tr>apple tart<tr><td>pie apple</td></tr></table>";
+    assert_eq!(
+        source_spans(html, "apple"),
+        owned(&[
+            ("apple", None),
+            ("apple", Some((15, "apple"))),
+            ("apple", Some((56, "apple"))),
+        ])
+    );
+    // CDATA sections are text in SVG and MathML, and bogus comments elsewhere
+    let html =
+        "<svg><text><![CDATA[<b>&amp;</b> apple]]> apple</text></svg><p><![CDATA[apple]]>apple</p>";
+    assert_eq!(
+        source_spans(html, "& apple"),
+        owned(&[
+            ("&", Some((23, "&"))),
+            ("apple", Some((33, "apple"))),
+            ("apple", Some((42, "apple"))),
+            ("apple", Some((80, "apple"))),
+        ])
+    );
+    let html = "<p>&amp;apple &#x61;pple &notit; &notin;a&#112;ple &AMP;apple &#x80;&#0;apple\r\napple &apple;</p>";
+    assert_eq!(
+        source_spans(html, "apple €"),
+        owned(&[
+            ("apple", Some((8, "apple"))),
+            ("apple", Some((14, "&#x61;pple"))),
+            ("apple", Some((40, "a&#112;ple"))),
+            ("apple", Some((56, "apple"))),
+            ("€", Some((62, "&#x80;"))),
+            ("apple", Some((72, "apple"))),
+            ("apple", Some((79, "apple"))),
+            ("apple", Some((86, "apple"))),
+        ])
+    );
+}
+
+#[test]
+fn splice_keeps_source_bytes() {
+    let html = "<P CLASS=intro>Fish &amp; chips, fish&nbsp;pie<br/>\r\n<img alt='fish'></P>";
+    let mut builder = Builder::new();
+    builder.search_term(Some("fish & chips"));
+    let spliced = builder.splice(html);
+    assert_eq!(
+        spliced.html,
+        "<P CLASS=intro><mark id=\"search-match\" data-searchlite=\"\">Fish</mark> <mark data-searchlite=\"\">&amp;</mark> \
+         <mark data-searchlite=\"\">chips</mark>, <mark data-searchlite=\"\">fish</mark>&nbsp;pie<br/>\r\n<img alt='fish'></P>"
+    );
+    assert_eq!(spliced.statistics.highlighted, 4);
+    assert_eq!(spliced.statistics.attribute_matches, 0);
+}
+
+#[test]
+fn splice_replaces_earlier_highlights() {
+    let mut builder = Builder::new();
+    builder.search_term(Some("apple"));
+    let once = builder.splice("<p>Q&A about apple pie</p>").html;
+    assert_eq!(
+        once,
+        "<p>Q&A about <mark id=\"search-match\" data-searchlite=\"\">apple</mark> pie</p>"
+    );
+    assert_eq!(builder.splice(&once).html, once);
+
+    let html = "<p><mark>apple</mark> <mark data-searchlite='' class=old>pie</mark></p>";
+    assert_eq!(
+        builder.splice(html).html,
+        "<p><mark><mark id=\"search-match\" data-searchlite=\"\">apple</mark></mark> pie</p>"
+    );
+}
+
+#[test]
+fn splice_reads_tags_with_non_ascii_attribute_names() {
+    let mut builder = Builder::new();
+    builder.search_term(Some("apple"));
+    let spliced = builder.splice(r#"<mark data-searchlite="">x</mark><p é=1 ü>apple</p>"#);
+    assert_eq!(
+        spliced.html,
+        r#"x<p é=1 ü><mark id="search-match" data-searchlite="">apple</mark></p>"#
+    );
+}
+
+#[test]
+fn serialization_options() {
+    let mut builder = Builder::new();
+    builder.search_term(Some("fish"));
+    let document = builder.search(
+        "<div>\n  <p>Fish&nbsp;&amp; chips<br><img src=a.png></p>\n  <pre>\n a</pre><script>1 < 2</script></div>",
+    );
+    let xhtml = SerializationOptions {
+        format: MarkupFormat::Xhtml,
+        entities: EntityStyle::Numeric,
+        ..SerializationOptions::default()
+    };
+    assert_eq!(
+        document.serialize(&xhtml),
+        "<div xmlns=\"http://www.w3.org/1999/xhtml\">\n  <p><mark id=\"search-match\" data-searchlite=\"\">Fish</mark>&#160;&#38; chips<br /><img src=\"a.png\" /></p>\n  \
+         <pre> a</pre><script>1 &#60; 2</script></div>"
+    );
+    assert_eq!(
+        builder
+            .search("<!-- a -- b ---><p>fish</p>text<svg><g/></svg>")
+            .serialize(&xhtml),
+        "<!-- a - - b - --><p xmlns=\"http://www.w3.org/1999/xhtml\">\
+         <mark id=\"search-match\" data-searchlite=\"\">fish</mark></p>text\
+         <svg xmlns=\"http://www.w3.org/2000/svg\"><g></g></svg>"
+    );
+    assert_eq!(
+        builder
+            .search(
+                "<svg><a xlink:href=#a><text>fish</text></a><use xlink:href=#b /></svg>\
+                 <svg xmlns:xlink=http://www.w3.org/1999/xlink><a xlink:href=#c /></svg>"
+            )
+            .serialize(&xhtml),
+        "<svg xmlns=\"http://www.w3.org/2000/svg\">\
+         <a xlink:href=\"#a\" xmlns:xlink=\"http://www.w3.org/1999/xlink\"><text>\
+         <mark id=\"search-match\" data-searchlite=\"\" xmlns=\"http://www.w3.org/1999/xhtml\">fish</mark></text></a>\
+         <use xlink:href=\"#b\" xmlns:xlink=\"http://www.w3.org/1999/xlink\"></use></svg>\
+         <svg xmlns:xlink=\"http://www.w3.org/1999/xlink\" xmlns=\"http://www.w3.org/2000/svg\">\
+         <a xlink:href=\"#c\"></a></svg>"
+    );
+    let pretty = SerializationOptions {
+        pretty: true,
+        scope: SerializationScope::IncludeRoot,
+        ..SerializationOptions::default()
+    };
+    assert_eq!(
+        document.serialize(&pretty),
+        "<html>\n  <div>\n    <p><mark id=\"search-match\" data-searchlite=\"\">Fish</mark>&nbsp;&amp; chips<br><img src=\"a.png\"></p>\n    \
+         <pre> a</pre><script>1 < 2</script>\n  </div>\n</html>"
+    );
+}
+
+#[test]
+fn cloned_dom_is_independent() {
+    let html = Html::parse(
+        "<!-- note --><p class=fruit>Apples and pears</p><template><b>apple</b></template>",
+    );
+    let search = |query| {
+        Builder::new()
+            .search_term(Some(query))
+            .search_dom(html.clone())
+            .to_string()
+    };
+    assert_eq!(
+        search("apple"),
+        "<!-- note --><p class=\"fruit\"><mark id=\"search-match\" data-searchlite=\"\">Apple</mark>s and pears</p>\
+         <template></template>"
+    );
+    assert_eq!(
+        search("pear"),
+        "<!-- note --><p class=\"fruit\">Apples and <mark id=\"search-match\" data-searchlite=\"\">pear</mark>s</p>\
+         <template></template>"
+    );
+
+    let template_contents = |html: &Html| {
+        let template = html.0.root().children.borrow()[2].clone();
+        let NodeData::Element {
+            ref template_contents,
+            ..
+        } = template.data
+        else {
+            panic!("expected the template element");
+        };
+        let contents = template_contents.borrow().clone();
+        contents.expect("template contents are parsed")
+    };
+    let original = template_contents(&html);
+    let cloned = html.clone();
+    let copy = template_contents(&cloned);
+    assert!(!Rc::ptr_eq(&original, &copy));
+    assert_eq!(format!("{copy:?}"), format!("{original:?}"));
+
+    // searching the clones left the original as it was parsed
+    assert_eq!(
+        Builder::new().search_dom(html).to_string(),
+        "<!-- note --><p class=\"fruit\">Apples and pears</p><template></template>"
+    );
+}
+
+#[test]
+fn incremental_search_matches_full_search() {
+    let html = "<h1>Apple pie</h1><p>An apple a day.</p><ul><li>Pineapple</li><li>Pears</li></ul>\
+                <img alt=\"Pear tart\"><p><mark data-searchlite=\"\">apple</mark> jam</p>\
+                <p>New<!-- edited --> apple</p>";
+    let parsed = Html::parse(html);
+    let mut incremental = IncrementalSearch::new(parsed.clone());
+    let queries = [
+        "a", "ap", "app", "apple", "apple ", "apple p", "apple pi", "appl", "pear", "pear t",
+        "new ap", "",
+    ];
+    // the second time round, a setting changing and the node budget running out part way
+    for (classes, node_budget) in [(vec![], None), (vec!["hit"], Some(12))] {
+        for query in queries {
+            let mut builder = Builder::new();
+            builder
+                .search_term(Some(query))
+                .match_attributes(HashSet::from(["alt"]))
+                .highlight_classes(classes.clone())
+                .node_budget(node_budget);
+            let document = incremental.search(&builder);
+            let expected = builder.search_dom(parsed.clone());
+            assert_eq!(
+                document.to_string(),
+                expected.to_string(),
+                "query {query:?}"
+            );
+            assert_eq!(
+                document.statistics(),
+                expected.statistics(),
+                "query {query:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn incremental_search_narrows_to_earlier_matches() {
+    let html = "<p>apple</p><p>apricot</p><p>banana</p><p>cherry</p>";
+    let mut incremental = IncrementalSearch::new(Html::parse(html));
+    let mut builder = Builder::new();
+    let mut searched = |builder: &Builder, query| {
+        let mut builder = builder.clone();
+        builder.search_term(Some(query));
+        let total = incremental.search(&builder).statistics().total;
+        (incremental.searched_nodes(), total)
+    };
+    // four paragraphs and their text, then the three texts with an `a`, then the two with `ap`
+    assert_eq!(searched(&builder, "a"), (8, 5));
+    assert_eq!(searched(&builder, "ap"), (3, 2));
+    assert_eq!(searched(&builder, "app"), (2, 1));
+    // an empty query looks at nothing, and the query after it at the whole document again
+    assert_eq!(searched(&builder, ""), (0, 0));
+    assert_eq!(searched(&builder, "a"), (8, 5));
+    // other settings changing starts again from the whole document
+    builder.whole_word(true);
+    assert_eq!(searched(&builder, "apple"), (8, 1));
+    assert_eq!(searched(&builder, "apples"), (8, 0));
+}
+
+#[test]
+fn incremental_search_only_replaces_changed_text() {
+    let html: String = (0..1_000)
+        .map(|index| match index % 100 {
+            0 => format!("<p>Paragraph {index} about apricots.</p>"),
+            _ => format!("<p>Paragraph {index} about pears.</p>"),
+        })
+        .collect();
+    let html = Html::parse(&html);
+    let paragraph =
+        |document: &Document, index: usize| document.dom.root().children.borrow()[index].clone();
+    let original_text = html.0.root().children.borrow()[1].children.borrow()[0].clone();
+    let mut incremental = IncrementalSearch::new(html);
+    let mut search = |query| {
+        let mut builder = Builder::new();
+        builder.search_term(Some(query));
+        let document = incremental.search(&builder);
+        let children: Vec<Handle> = paragraph(document, 1).children.borrow().clone();
+        let highlighted: Vec<Handle> = paragraph(document, 100).children.borrow().clone();
+        (incremental.searched_nodes(), children, highlighted)
+    };
+
+    let (full, children, _) = search("a");
+    assert_eq!(full, 2_000);
+    assert!(!Rc::ptr_eq(&children[0], &original_text));
+    // every paragraph has an `a`, but only ten have an `apr`, and then those are all searched
+    let (searched, children, highlighted) = search("apr");
+    assert_eq!(searched, 1_000);
+    assert!(children.len() == 1 && Rc::ptr_eq(&children[0], &original_text));
+    let (searched, children, unchanged) = search("apr ");
+    assert_eq!(searched, 10);
+    assert!(children.len() == 1 && Rc::ptr_eq(&children[0], &original_text));
+    // the same highlights are left as they were
+    assert_eq!(unchanged.len(), highlighted.len());
+    assert!(unchanged
+        .iter()
+        .zip(&highlighted)
+        .all(|(unchanged, highlighted)| Rc::ptr_eq(unchanged, highlighted)));
+    let (searched, _, changed) = search("apri");
+    assert_eq!(searched, 10);
+    assert!(!Rc::ptr_eq(&changed[1], &highlighted[1]));
+}
@@ -0,0 +1,267 @@
+//! Transliteration into Latin script, so a Latin-alphabet query matches Serbian Cyrillic text
+//! and a romaji query matches kana.
+
+use super::folding::FoldedText;
+
+/// Rewrites Serbian Cyrillic into Gaj's Latin alphabet and hiragana and katakana into Hepburn
+/// romaji (written as typed on a keyboard, so `とうきょう` becomes `toukyou`). Other characters
+/// are kept as they are.
+pub(crate) fn transliterate(text: &str) -> FoldedText {
+    let mut folded = FoldedText::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let end = start + c.len_utf8();
+        if let Some(latin) = serbian_cyrillic(c) {
+            folded.push(latin, start..end);
+            continue;
+        }
+        let c = to_hiragana(c);
+        let next = chars
+            .peek()
+            .map(|&(index, next)| (index, to_hiragana(next)));
+        if let Some(romaji) = romaji(c) {
+            // a kana followed by a small kana combining with it, such as きゃ or ふぁ
+            let combined = next.and_then(|(index, next)| {
+                combine(romaji, next).map(|combined| (combined, index + next.len_utf8()))
+            });
+            match combined {
+                Some((combined, end)) => {
+                    folded.push(&combined, start..end);
+                    chars.next();
+                }
+                None => folded.push(romaji, start..end),
+            }
+        } else if c == 'っ' {
+            // doubles the consonant which follows, with っち written `tchi`
+            let consonant = next
+                .and_then(|(_, next)| romaji(next))
+                .and_then(|romaji| romaji.chars().next())
+                .filter(|first| !is_vowel(*first))
+                .map(|first| if first == 'c' { 't' } else { first });
+            if let Some(consonant) = consonant {
+                folded.push(consonant.encode_utf8(&mut [0; 4]), start..end);
+            }
+        } else if c == 'ー' {
+            // lengthens the vowel before
+            let vowel = folded
+                .text
+                .chars()
+                .next_back()
+                .filter(|last| is_vowel(*last));
+            if let Some(vowel) = vowel {
+                folded.push(vowel.encode_utf8(&mut [0; 4]), start..end);
+            }
+        } else {
+            folded.push(c.encode_utf8(&mut [0; 4]), start..end);
+        }
+    }
+    folded
+}
+
+/// Spells the lowercase Serbian Latin letters with diacritics as they are often typed without
+/// them, so `sabac` matches `šabac`: `č` and `ć` as `c`, `š` as `s`, `ž` as `z` and `đ` as `dj`.
+pub(crate) fn strip_diacritics(text: &str) -> FoldedText {
+    let mut folded = FoldedText::with_capacity(text.len());
+    for (start, c) in text.char_indices() {
+        let end = start + c.len_utf8();
+        match c {
+            'č' | 'ć' => folded.push("c", start..end),
+            'š' => folded.push("s", start..end),
+            'ž' => folded.push("z", start..end),
+            'đ' => folded.push("dj", start..end),
+            _ => folded.push(c.encode_utf8(&mut [0; 4]), start..end),
+        }
+    }
+    folded
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'i' | 'u' | 'e' | 'o')
+}
+
+fn to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/*
+ * Romaji for `romaji` followed by the small kana `next`, when they combine: ゃ, ゅ and ょ after
+ * an i-row kana (`kya`, `sha`) and small vowels after a kana with a consonant (`fa`, `che`).
+ */
+fn combine(romaji: &str, next: char) -> Option<String> {
+    let stem = &romaji[..romaji.len() - 1];
+    if stem.is_empty() {
+        return None;
+    }
+    match next {
+        'ゃ' | 'ゅ' | 'ょ' if romaji.ends_with('i') => {
+            let vowel = &small_vowel(next)?[1..];
+            Some(match stem {
+                "sh" | "ch" | "j" => format!("{stem}{vowel}"),
+                _ => format!("{stem}y{vowel}"),
+            })
+        }
+        'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' => Some(format!("{stem}{}", small_vowel(next)?)),
+        _ => None,
+    }
+}
+
+fn small_vowel(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ぁ' => "a",
+        'ぃ' => "i",
+        'ぅ' => "u",
+        'ぇ' => "e",
+        'ぉ' => "o",
+        'ゃ' => "ya",
+        'ゅ' => "yu",
+        'ょ' => "yo",
+        _ => return None,
+    })
+}
+
+fn romaji(c: char) -> Option<&'static str> {
+    Some(match c {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' => "e",
+        'お' | 'ぉ' => "o",
+        'か' | 'ゕ' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' | 'ゖ' => "ke",
+        'こ' => "ko",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'ざ' => "za",
+        'じ' | 'ぢ' => "ji",
+        'ず' | 'づ' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'だ' => "da",
+        'で' => "de",
+        'ど' => "do",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' | 'ゃ' => "ya",
+        'ゆ' | 'ゅ' => "yu",
+        'よ' | 'ょ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' | 'ゎ' => "wa",
+        'ゐ' => "wi",
+        'ゑ' => "we",
+        'を' => "wo",
+        'ん' => "n",
+        'ゔ' => "vu",
+        _ => return None,
+    })
+}
+
+fn serbian_cyrillic(c: char) -> Option<&'static str> {
+    Some(match c {
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' => "g",
+        'д' => "d",
+        'ђ' => "đ",
+        'е' => "e",
+        'ж' => "ž",
+        'з' => "z",
+        'и' => "i",
+        'ј' => "j",
+        'к' => "k",
+        'л' => "l",
+        'љ' => "lj",
+        'м' => "m",
+        'н' => "n",
+        'њ' => "nj",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'ћ' => "ć",
+        'у' => "u",
+        'ф' => "f",
+        'х' => "h",
+        'ц' => "c",
+        'ч' => "č",
+        'џ' => "dž",
+        'ш' => "š",
+        'А' => "A",
+        'Б' => "B",
+        'В' => "V",
+        'Г' => "G",
+        'Д' => "D",
+        'Ђ' => "Đ",
+        'Е' => "E",
+        'Ж' => "Ž",
+        'З' => "Z",
+        'И' => "I",
+        'Ј' => "J",
+        'К' => "K",
+        'Л' => "L",
+        'Љ' => "Lj",
+        'М' => "M",
+        'Н' => "N",
+        'Њ' => "Nj",
+        'О' => "O",
+        'П' => "P",
+        'Р' => "R",
+        'С' => "S",
+        'Т' => "T",
+        'Ћ' => "Ć",
+        'У' => "U",
+        'Ф' => "F",
+        'Х' => "H",
+        'Ц' => "C",
+        'Ч' => "Č",
+        'Џ' => "Dž",
+        'Ш' => "Š",
+        _ => return None,
+    })
+}
@@ -1,41 +1,41 @@
+//! Highlight search terms in HTML.
+//!
+//! Searchlite parses an HTML fragment with `html5ever`, wraps each occurrence of the search
+//! terms in a `<mark>` element and serialises the result. Matching is ASCII case-insensitive and
+//! multiple terms are separated by spaces.
+//!
+//! ```
+//! use searchlite::Builder;
+//!
+//! let document = Builder::new()
+//!     .search_term(Some("apple"))
+//!     .search("<p>Nobody likes maple in their apple flavoured Snapple.</p>");
+//! assert_eq!(
+//!     document.to_string(),
+//!     r#"<p>Nobody likes maple in their <mark id="search-match" data-searchlite="">apple</mark> flavoured Sn<mark data-searchlite="">apple</mark>.</p>"#
+//! );
+//! assert!(document.diagnostics().errors.is_empty());
+//! ```
+//!
+//! The `wasm` feature (enabled by default) adds the JavaScript bindings in [`wasm`]. Disable
+//! default features to use searchlite from native Rust code without pulling in `wasm-bindgen`.
+
 mod html_search;
+#[cfg(feature = "wasm")]
 mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-//use html2text::from_read;
-use html_search::search_html;
-use wasm_bindgen::prelude::*;
+pub use html_search::{
+    highlight_text, remove_highlights, search_html, Builder, Diagnostics, Document, EntityStyle,
+    HighlightRange, HighlightRanges, Html, IncrementalSearch, MarkupFormat, MatchKind,
+    MatchStatistics, Normalization, Offset, ParseError, QuirksMode, SearchTerm, Segmentation,
+    SerializationOptions, SerializationScope, SourcePosition, SourceSpan, SplicedHtml,
+    TermStatistics, IGNORED_CHARACTERS, SKIP_TAGS,
+};
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
-
-#[wasm_bindgen]
-extern "C" {
-    // Use `js_namespace` here to bind `console.log(..)` instead of just
-    // `log(..)`
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
-}
-
-// macro_rules! console_log {
-//      ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
-//  }
-
-#[wasm_bindgen]
-extern "C" {
-    fn alert(s: &str);
-}
-
-#[wasm_bindgen]
-pub fn highlight_search_terms(input_string: &str, search_pattern: &str) -> String {
-    search_html(input_string, search_pattern)
-}
-
-//#[wasm_bindgen]
-//pub fn html_to_text(html: &str) -> String {
-//    let output_text_columns: usize = 72;
-//    let text = from_read(html.as_bytes(), output_text_columns);
-//    text
-//}
@@ -0,0 +1,541 @@
+//! `searchlite` command-line tool: highlights search terms in HTML read from files or standard
+//! input, or reports match statistics or text snippets for them.
+
+use clap::{Parser, ValueEnum};
+use searchlite::{
+    Builder, Document, Html, MatchKind, MatchStatistics, Normalization, SearchTerm, Segmentation,
+};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Output {
+    /// Highlighted HTML
+    Html,
+    /// Match statistics as JSON
+    Stats,
+    /// Plain-text excerpts around each match, one per line
+    Snippets,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum MatchKindArg {
+    /// The match which ends first wins
+    Standard,
+    /// The match which starts first wins, then the longest
+    LeftmostLongest,
+    /// The match which starts first wins, then the term given first
+    LeftmostFirst,
+    /// Leftmost-longest, with matches of other terms inside them highlighted too
+    Nested,
+}
+
+impl From<MatchKindArg> for MatchKind {
+    fn from(value: MatchKindArg) -> Self {
+        match value {
+            MatchKindArg::Standard => MatchKind::Standard,
+            MatchKindArg::LeftmostLongest => MatchKind::LeftmostLongest,
+            MatchKindArg::LeftmostFirst => MatchKind::LeftmostFirst,
+            MatchKindArg::Nested => MatchKind::Nested,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SegmentationArg {
+    /// Split terms at whitespace only
+    Whitespace,
+    /// Also split Chinese, Japanese, Thai and other unspaced text into overlapping character pairs
+    Bigram,
+}
+
+impl From<SegmentationArg> for Segmentation {
+    fn from(value: SegmentationArg) -> Self {
+        match value {
+            SegmentationArg::Whitespace => Segmentation::Whitespace,
+            SegmentationArg::Bigram => Segmentation::Bigram,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum NormalizationArg {
+    /// Canonical composition
+    Nfc,
+    /// Compatibility composition, also folding full-width characters and ligatures
+    Nfkc,
+}
+
+impl From<NormalizationArg> for Normalization {
+    fn from(value: NormalizationArg) -> Self {
+        match value {
+            NormalizationArg::Nfc => Normalization::Nfc,
+            NormalizationArg::Nfkc => Normalization::Nfkc,
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Highlight search terms in HTML")]
+struct Cli {
+    /// Search terms, separated by whitespace
+    #[arg(short, long, required_unless_present = "terms")]
+    query: Option<String>,
+
+    /// A single search term, which may contain spaces (repeat for more than one)
+    #[arg(short, long = "term")]
+    terms: Vec<String>,
+
+    /// HTML files, or directories to search for `.html` files in. Reads standard input when
+    /// omitted
+    paths: Vec<PathBuf>,
+
+    /// What to write to standard output (or `--out-dir`)
+    #[arg(short, long, value_enum, default_value_t = Output::Html)]
+    output: Output,
+
+    /// Write highlighted HTML here, mirroring the input paths, instead of to standard output.
+    /// Required when highlighting more than one file
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+
+    /// Parse input as full HTML documents (with doctype, head and body) rather than fragments
+    #[arg(long)]
+    document: bool,
+
+    /// Read input as Markdown, rendering it to HTML. Directories are searched for `.md` files
+    #[cfg(feature = "markdown")]
+    #[arg(long, conflicts_with = "document")]
+    markdown: bool,
+
+    /// Search inside Markdown code blocks
+    #[cfg(feature = "markdown")]
+    #[arg(long, requires = "markdown")]
+    markdown_code_blocks: bool,
+
+    /// Insert highlights into the input as written, leaving every other byte unchanged, instead
+    /// of writing out the parsed HTML. Attributes cannot be searched. Has no effect on Markdown
+    /// input
+    #[arg(long, conflicts_with_all = ["match_attributes", "attribute_match_class"])]
+    preserve_source: bool,
+
+    /// Match the case of search terms exactly
+    #[arg(long)]
+    case_sensitive: bool,
+
+    /// Only highlight matches which are whole words
+    #[arg(long)]
+    whole_word: bool,
+
+    /// How overlapping matches of different terms are resolved
+    #[arg(long, value_enum, default_value_t = MatchKindArg::Standard)]
+    match_kind: MatchKindArg,
+
+    /// How query terms in scripts written without spaces are broken up
+    #[arg(long, value_enum, default_value_t = SegmentationArg::Whitespace)]
+    segmentation: SegmentationArg,
+
+    /// Character skipped over when matching (repeat for more than one). Replaces the defaults:
+    /// soft hyphen and zero-width characters
+    #[arg(long = "ignore-character")]
+    ignored_characters: Vec<char>,
+
+    /// Match every character, including soft hyphens and zero-width characters
+    #[arg(long, conflicts_with = "ignored_characters")]
+    match_invisible: bool,
+
+    /// Match whitespace in terms literally, instead of treating any run of whitespace as one space
+    #[arg(long)]
+    literal_whitespace: bool,
+
+    /// Unicode normalisation applied to terms and text before matching
+    #[arg(long, value_enum)]
+    normalization: Option<NormalizationArg>,
+
+    /// Match across scripts by transliterating terms and text into Latin script
+    #[cfg(feature = "transliteration")]
+    #[arg(long)]
+    transliterate: bool,
+
+    /// Element wrapped around matches
+    #[arg(long, default_value = "mark")]
+    tag: String,
+
+    /// Class added to every highlight element (repeat for more than one)
+    #[arg(long = "class")]
+    classes: Vec<String>,
+
+    /// Element whose content is not searched (repeat for more than one). Replaces the defaults:
+    /// head, script, style, template, textarea and title
+    #[arg(long = "skip-tag")]
+    skip_tags: Vec<String>,
+
+    /// Attribute added to every inserted `<mark>`, to tell highlights apart from author marks.
+    /// Defaults to data-searchlite
+    #[arg(long)]
+    marker_attribute: Option<String>,
+
+    /// Attribute whose value is also searched (repeat for more than one)
+    #[arg(long = "match-attribute")]
+    match_attributes: Vec<String>,
+
+    /// Class added to elements with a matching attribute
+    #[arg(long)]
+    attribute_match_class: Option<String>,
+
+    /// Most matches to highlight in each input, across all terms. Later matches are still counted
+    #[arg(long)]
+    max_highlights: Option<usize>,
+
+    /// Most matches to highlight in each input for any one term
+    #[arg(long)]
+    max_highlights_per_term: Option<usize>,
+
+    /// Most nodes to visit in each input before leaving the rest unsearched
+    #[arg(long)]
+    node_budget: Option<usize>,
+
+    /// Most milliseconds to spend on each input before leaving the rest unsearched
+    #[arg(long)]
+    time_budget_ms: Option<u64>,
+
+    /// Drop terms shorter than this many characters
+    #[arg(long, default_value_t = 1)]
+    min_term_length: usize,
+
+    /// Strip punctuation from the start and end of each term
+    #[arg(long)]
+    trim_punctuation: bool,
+
+    /// Drop terms which repeat an earlier term
+    #[arg(long)]
+    deduplicate_terms: bool,
+
+    /// Most terms to search for; later terms are ignored
+    #[arg(long)]
+    max_terms: Option<usize>,
+
+    /// Characters of context either side of each match in snippets
+    #[arg(long, default_value_t = 40)]
+    context: usize,
+
+    /// Exit with an error status when nothing matches across all inputs
+    #[arg(long)]
+    require_match: bool,
+}
+
+struct Input {
+    /// Path to read from, or `None` for standard input.
+    source: Option<PathBuf>,
+    /// Path relative to `--out-dir` to write highlighted output to.
+    relative_path: PathBuf,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InputStatistics {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    #[serde(flatten)]
+    statistics: MatchStatistics,
+}
+
+impl Cli {
+    #[cfg(feature = "markdown")]
+    fn markdown(&self) -> bool {
+        self.markdown
+    }
+
+    #[cfg(not(feature = "markdown"))]
+    fn markdown(&self) -> bool {
+        false
+    }
+}
+
+fn is_input_file(path: &Path, markdown: bool) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    if markdown {
+        matches!(extension, Some("md" | "markdown"))
+    } else {
+        matches!(extension, Some("html" | "htm"))
+    }
+}
+
+/*
+ * Where under `--out-dir` to write the output for `path`, a file named on the command line: the
+ * path as given when it is relative and stays below the current directory, so that files with the
+ * same name in different directories are kept apart, and just the file name otherwise.
+ */
+fn relative_output_path(path: &Path) -> PathBuf {
+    let components = path
+        .components()
+        .filter(|component| *component != Component::CurDir);
+    if components
+        .clone()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        components.collect()
+    } else {
+        path.file_name().map(PathBuf::from).unwrap_or_default()
+    }
+}
+
+/*
+ * Expands `paths` into the files to process: files are used as given and directories are walked
+ * (in sorted order) for files with an `.html` or `.htm` extension (`.md` or `.markdown` for
+ * Markdown).
+ */
+fn collect_inputs(paths: &[PathBuf], markdown: bool) -> io::Result<Vec<Input>> {
+    if paths.is_empty() {
+        return Ok(vec![Input {
+            source: None,
+            relative_path: PathBuf::from("stdin.html"),
+        }]);
+    }
+    let mut inputs = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            inputs.push(Input {
+                source: Some(path.clone()),
+                relative_path: relative_output_path(path),
+            });
+            continue;
+        }
+        let mut directories = vec![path.clone()];
+        let mut files = Vec::new();
+        while let Some(directory) = directories.pop() {
+            for entry in fs::read_dir(&directory)? {
+                let entry_path = entry?.path();
+                if entry_path.is_dir() {
+                    directories.push(entry_path);
+                } else if is_input_file(&entry_path, markdown) {
+                    files.push(entry_path);
+                }
+            }
+        }
+        files.sort();
+        inputs.extend(files.into_iter().map(|file| {
+            Input {
+                relative_path: file
+                    .strip_prefix(path)
+                    .map(Path::to_path_buf)
+                    .unwrap_or_else(|_| file.clone()),
+                source: Some(file),
+            }
+        }));
+    }
+    if markdown {
+        // highlighted Markdown is written out as HTML
+        for input in &mut inputs {
+            input.relative_path.set_extension("html");
+        }
+    }
+    Ok(inputs)
+}
+
+/*
+ * Fails if two inputs would be written to the same path under `--out-dir`, rather than have the
+ * later overwrite the earlier.
+ */
+fn check_output_paths(inputs: &[Input]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for input in inputs {
+        if !seen.insert(&input.relative_path) {
+            return Err(format!(
+                "more than one input would be written to `{}` in `--out-dir`",
+                input.relative_path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn read_input(input: &Input) -> io::Result<String> {
+    match input.source {
+        Some(ref path) => fs::read_to_string(path),
+        None => {
+            let mut buffer = String::new();
+            io::stdin().read_to_string(&mut buffer)?;
+            Ok(buffer)
+        }
+    }
+}
+
+fn parse(src: &str, document: bool) -> Html {
+    if document {
+        Html::parse_document(src)
+    } else {
+        Html::parse(src)
+    }
+}
+
+fn search(builder: &Builder, src: &str, cli: &Cli) -> Document {
+    #[cfg(feature = "markdown")]
+    if cli.markdown {
+        return builder.search_markdown(src);
+    }
+    builder.search_dom(parse(src, cli.document))
+}
+
+/*
+ * Highlights `src` as `search` does, or by splicing highlights into it with `--preserve-source`,
+ * returning the HTML to write (serialised only if it is written) and the match counts.
+ */
+fn highlight(builder: &Builder, src: &str, cli: &Cli) -> (Box<dyn Display>, MatchStatistics) {
+    if cli.preserve_source && !cli.markdown() {
+        let spliced = if cli.document {
+            builder.splice_document(src)
+        } else {
+            builder.splice(src)
+        };
+        return (Box::new(spliced.html), spliced.statistics);
+    }
+    let document = search(builder, src, cli);
+    let statistics = document.statistics().clone();
+    (Box::new(document), statistics)
+}
+
+fn snippets(builder: &Builder, src: &str, cli: &Cli) -> Vec<String> {
+    #[cfg(feature = "markdown")]
+    if cli.markdown {
+        return builder.snippets_markdown(src, cli.context);
+    }
+    builder.snippets_dom(parse(src, cli.document), cli.context)
+}
+
+/*
+ * Processes every input, writing output as it goes. Returns whether any input contained a match.
+ */
+fn run(cli: &Cli) -> Result<bool, String> {
+    if cli.out_dir.is_some() && cli.output != Output::Html {
+        return Err("`--out-dir` only applies to `--output html`".to_string());
+    }
+    let inputs = collect_inputs(&cli.paths, cli.markdown()).map_err(|error| error.to_string())?;
+    if cli.output == Output::Html && inputs.len() > 1 && cli.out_dir.is_none() {
+        return Err("`--out-dir` is needed to highlight more than one file".to_string());
+    }
+    if cli.output == Output::Html && cli.out_dir.is_some() {
+        check_output_paths(&inputs)?;
+    }
+
+    let match_attributes: HashSet<&str> = cli.match_attributes.iter().map(String::as_str).collect();
+    let mut builder = Builder::new();
+    builder
+        .search_term(cli.query.as_deref())
+        .search_terms(
+            cli.terms
+                .iter()
+                .map(|term| SearchTerm::new(term.as_str()))
+                .collect(),
+        )
+        .case_sensitive(cli.case_sensitive)
+        .whole_word(cli.whole_word)
+        .match_kind(cli.match_kind.into())
+        .segmentation(cli.segmentation.into())
+        .collapse_whitespace(!cli.literal_whitespace)
+        .normalization(cli.normalization.map(Normalization::from))
+        .highlight_tag(&cli.tag)
+        .highlight_classes(cli.classes.iter().map(String::as_str).collect())
+        .match_attributes(match_attributes)
+        .attribute_match_class(cli.attribute_match_class.as_deref())
+        .max_highlights(cli.max_highlights)
+        .max_highlights_per_term(cli.max_highlights_per_term)
+        .node_budget(cli.node_budget)
+        .time_budget(cli.time_budget_ms.map(Duration::from_millis))
+        .min_term_length(cli.min_term_length)
+        .trim_punctuation(cli.trim_punctuation)
+        .deduplicate_terms(cli.deduplicate_terms)
+        .max_terms(cli.max_terms);
+    #[cfg(feature = "transliteration")]
+    builder.transliterate(cli.transliterate);
+    if cli.match_invisible || !cli.ignored_characters.is_empty() {
+        builder.ignored_characters(cli.ignored_characters.iter().copied().collect());
+    }
+    #[cfg(feature = "markdown")]
+    builder.markdown_code_blocks(cli.markdown_code_blocks);
+    if !cli.skip_tags.is_empty() {
+        builder.skip_tags(cli.skip_tags.iter().map(String::as_str).collect());
+    }
+    if let Some(ref marker_attribute) = cli.marker_attribute {
+        builder.marker_attribute((marker_attribute, ""));
+    }
+
+    let mut stdout = io::stdout().lock();
+    let mut statistics = Vec::new();
+    let mut matched = false;
+    for input in &inputs {
+        let display_path = input.source.as_deref().map(Path::display);
+        let src = read_input(input).map_err(|error| match display_path {
+            Some(ref path) => format!("{path}: {error}"),
+            None => error.to_string(),
+        })?;
+        match cli.output {
+            Output::Html | Output::Stats => {
+                let (html, input_statistics) = highlight(&builder, &src, cli);
+                matched |= input_statistics.total + input_statistics.attribute_matches > 0;
+                if cli.output == Output::Stats {
+                    statistics.push(InputStatistics {
+                        path: input.source.clone(),
+                        statistics: input_statistics,
+                    });
+                } else if let Some(ref out_dir) = cli.out_dir {
+                    let out_path = out_dir.join(&input.relative_path);
+                    if let Some(parent) = out_path.parent() {
+                        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+                    }
+                    fs::write(&out_path, html.to_string())
+                        .map_err(|error| format!("{}: {error}", out_path.display()))?;
+                } else {
+                    write!(stdout, "{html}").map_err(|error| error.to_string())?;
+                }
+            }
+            Output::Snippets => {
+                for snippet in snippets(&builder, &src, cli) {
+                    matched = true;
+                    match display_path {
+                        Some(ref path) => writeln!(stdout, "{path}:{snippet}"),
+                        None => writeln!(stdout, "{snippet}"),
+                    }
+                    .map_err(|error| error.to_string())?;
+                }
+            }
+        }
+    }
+
+    if cli.output == Output::Stats {
+        let json = if cli.paths.is_empty() {
+            serde_json::to_string_pretty(&statistics[0])
+        } else {
+            serde_json::to_string_pretty(&statistics)
+        }
+        .map_err(|error| error.to_string())?;
+        writeln!(stdout, "{json}").map_err(|error| error.to_string())?;
+    }
+    Ok(matched)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(matched) => {
+            if cli.require_match && !matched {
+                eprintln!("No matches found");
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Err(message) => {
+            eprintln!("searchlite: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,573 @@
+//! [wasm-bindgen](https://rustwasm.github.io/docs/wasm-bindgen/) bindings for the JavaScript
+//! API. These are a thin layer over [`crate::Builder`], enabled by the `wasm` feature.
+
+use crate::{
+    remove_highlights, search_html, Builder, Diagnostics, Document, HighlightRange,
+    HighlightRanges, Html, IncrementalSearch, MatchKind, MatchStatistics, Normalization,
+    SearchTerm, Segmentation, SerializationOptions,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+/// Runs when the module is instantiated, sending panic messages to `console.error` when the
+/// `console_error_panic_hook` feature is enabled.
+#[wasm_bindgen(start)]
+fn start() {
+    crate::utils::set_panic_hook();
+}
+
+/// Highlights `search_pattern` in `input_string`. Every inserted `<mark>` carries an empty
+/// `data-searchlite` attribute, so highlights can be told apart from author `<mark>` elements
+/// when they are removed again. Use `highlight` for any other setting.
+#[wasm_bindgen]
+pub fn highlight_search_terms(input_string: &str, search_pattern: &str) -> String {
+    search_html(input_string, search_pattern)
+}
+
+/// Removes `<mark>` highlights a previous call to `highlight_search_terms` or `highlight`
+/// inserted, merging the text they wrapped back into the surrounding text. Pass the same
+/// `markerAttribute` used for highlighting, if any.
+#[wasm_bindgen]
+pub fn remove_search_highlights(input_string: &str, marker_attribute: Option<String>) -> String {
+    match marker_attribute {
+        Some(marker_attribute) => Builder::new()
+            .marker_attribute((&marker_attribute, ""))
+            .unhighlight(input_string)
+            .to_string(),
+        None => remove_highlights(input_string),
+    }
+}
+
+/// Settings for `highlight`. Every field is optional.
+#[derive(Debug, Default, Deserialize, Tsify)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct HighlightOptions {
+    /// Match the case of search terms exactly, instead of ignoring ASCII case.
+    #[serde(default)]
+    case_sensitive: bool,
+    /// Only highlight matches which are whole words.
+    #[serde(default)]
+    whole_word: bool,
+    /// How overlapping matches of different terms are resolved (`"standard"` by default).
+    #[serde(default)]
+    match_kind: MatchKind,
+    /// How query terms in scripts written without spaces (Chinese, Japanese, Thai and so on) are
+    /// broken up: `"whitespace"` (default) or `"bigram"`.
+    #[serde(default)]
+    segmentation: Segmentation,
+    /// Characters skipped over when matching, replacing the defaults (soft hyphen and zero-width
+    /// characters). An empty array matches every character.
+    #[serde(default)]
+    ignored_characters: Option<Vec<char>>,
+    /// Treat any run of whitespace as a single space when matching (`true` by default).
+    #[serde(default)]
+    collapse_whitespace: Option<bool>,
+    /// Unicode normalisation applied before matching: `"nfc"` or `"nfkc"`. None by default.
+    #[serde(default)]
+    normalization: Option<Normalization>,
+    /// Match across scripts by transliterating terms and text into Latin script (Serbian
+    /// Cyrillic and Japanese kana). Needs the `transliteration` cargo feature.
+    #[cfg(feature = "transliteration")]
+    #[serde(default)]
+    transliterate: bool,
+    /// Element wrapped around matches (`"mark"` by default).
+    #[serde(default)]
+    tag: Option<String>,
+    /// Classes added to every highlight element.
+    #[serde(default)]
+    classes: Vec<String>,
+    /// Elements whose content is not searched, replacing the default list (`head`, `script`,
+    /// `style`, `template`, `textarea` and `title`).
+    #[serde(default)]
+    skip_tags: Option<Vec<String>>,
+    /// Attribute added to every highlight element, to tell highlights apart from author marks.
+    /// Defaults to `data-searchlite`.
+    #[serde(default)]
+    marker_attribute: Option<String>,
+    /// Attributes whose values are also searched.
+    #[serde(default)]
+    match_attributes: Vec<String>,
+    /// Class added to elements with a matching attribute.
+    #[serde(default)]
+    attribute_match_class: Option<String>,
+    /// Parse the input as a full document, rather than a fragment.
+    #[serde(default)]
+    document: bool,
+    /// Insert highlights into the input as written, leaving every other byte unchanged, instead
+    /// of returning the parsed HTML serialised again. Attribute matches are not annotated. Has no
+    /// effect on Markdown input.
+    #[serde(default)]
+    preserve_source: bool,
+    /// How the highlighted HTML is written out: `format` (`"html"` or `"xhtml"`), `entities`
+    /// (`"named"` or `"numeric"`), `scope` (`"children"` or `"include-root"`) and `pretty`. Not
+    /// used with `preserveSource`, which keeps the input as written.
+    #[serde(default)]
+    serialization: SerializationOptions,
+    /// Read the input as Markdown, rendering it to HTML before highlighting. Needs the
+    /// `markdown` cargo feature.
+    #[cfg(feature = "markdown")]
+    #[serde(default)]
+    markdown: bool,
+    /// Search inside Markdown code blocks, which are skipped by default.
+    #[cfg(feature = "markdown")]
+    #[serde(default)]
+    markdown_code_blocks: bool,
+    /// Most matches to highlight, across all terms. Later matches are still counted.
+    #[serde(default)]
+    max_highlights: Option<usize>,
+    /// Most matches to highlight for any one term. Later matches are still counted.
+    #[serde(default)]
+    max_highlights_per_term: Option<usize>,
+    /// Most nodes to visit before giving up on the rest of the input.
+    #[serde(default)]
+    node_budget: Option<usize>,
+    /// Most milliseconds to spend before giving up on the rest of the input.
+    #[serde(default)]
+    time_budget_ms: Option<f64>,
+    /// Terms shorter than this many characters are dropped (1 by default).
+    #[serde(default)]
+    min_term_length: Option<usize>,
+    /// Strip punctuation from the start and end of each term.
+    #[serde(default)]
+    trim_punctuation: bool,
+    /// Drop terms which repeat an earlier term.
+    #[serde(default)]
+    deduplicate_terms: bool,
+    /// Most terms to search for; later terms are ignored.
+    #[serde(default)]
+    max_terms: Option<usize>,
+}
+
+/// Search terms: either a single string of whitespace-separated terms, or an array with one
+/// entry per term.
+#[derive(Debug, Deserialize, Tsify)]
+#[serde(untagged)]
+pub enum Query {
+    Text(String),
+    Terms(Vec<QueryTerm>),
+}
+
+/// A single search term: just its text, or a `SearchTerm` with its own matching options.
+#[derive(Debug, Deserialize, Tsify)]
+#[serde(untagged)]
+pub enum QueryTerm {
+    Text(String),
+    Term(SearchTerm),
+}
+
+impl From<&QueryTerm> for SearchTerm {
+    fn from(value: &QueryTerm) -> Self {
+        match value {
+            QueryTerm::Text(text) => SearchTerm::new(text.as_str()),
+            QueryTerm::Term(term) => term.clone(),
+        }
+    }
+}
+
+impl HighlightOptions {
+    fn builder<'a>(&'a self, query: &'a Query) -> Builder<'a> {
+        let mut builder = Builder::new();
+        match query {
+            Query::Text(search_pattern) => builder.search_term(Some(search_pattern)),
+            Query::Terms(terms) => {
+                builder.search_terms(terms.iter().map(SearchTerm::from).collect())
+            }
+        };
+        builder
+            .case_sensitive(self.case_sensitive)
+            .whole_word(self.whole_word)
+            .match_kind(self.match_kind)
+            .segmentation(self.segmentation)
+            .normalization(self.normalization)
+            .highlight_classes(self.classes.iter().map(String::as_str).collect())
+            .match_attributes(self.match_attributes.iter().map(String::as_str).collect())
+            .attribute_match_class(self.attribute_match_class.as_deref())
+            .max_highlights(self.max_highlights)
+            .max_highlights_per_term(self.max_highlights_per_term)
+            .node_budget(self.node_budget)
+            .time_budget(self.time_budget_ms.and_then(|milliseconds| {
+                Duration::try_from_secs_f64(milliseconds.max(0.0) / 1000.0).ok()
+            }))
+            .trim_punctuation(self.trim_punctuation)
+            .deduplicate_terms(self.deduplicate_terms)
+            .max_terms(self.max_terms);
+        #[cfg(feature = "transliteration")]
+        builder.transliterate(self.transliterate);
+        #[cfg(feature = "markdown")]
+        builder.markdown_code_blocks(self.markdown_code_blocks);
+        if let Some(min_term_length) = self.min_term_length {
+            builder.min_term_length(min_term_length);
+        }
+        if let Some(ref tag) = self.tag {
+            builder.highlight_tag(tag);
+        }
+        if let Some(ref marker_attribute) = self.marker_attribute {
+            builder.marker_attribute((marker_attribute, ""));
+        }
+        if let Some(collapse_whitespace) = self.collapse_whitespace {
+            builder.collapse_whitespace(collapse_whitespace);
+        }
+        if let Some(ref ignored_characters) = self.ignored_characters {
+            builder.ignored_characters(ignored_characters.iter().copied().collect());
+        }
+        if let Some(ref skip_tags) = self.skip_tags {
+            builder.skip_tags(skip_tags.iter().map(String::as_str).collect());
+        }
+        builder
+    }
+
+    /*
+     * Parses `input_string` as a fragment, full document or Markdown, as set, and finds where
+     * matches are without highlighting them.
+     */
+    fn ranges(&self, builder: &Builder, input_string: &str) -> HighlightRanges {
+        #[cfg(feature = "markdown")]
+        if self.markdown {
+            return builder.ranges_markdown(input_string);
+        }
+        if self.document {
+            builder.ranges_document(input_string)
+        } else {
+            builder.ranges(input_string)
+        }
+    }
+
+    /*
+     * Parses `input_string` as a fragment, full document or Markdown, as set, and highlights it.
+     */
+    fn search(&self, builder: &Builder, input_string: &str) -> Document {
+        #[cfg(feature = "markdown")]
+        if self.markdown {
+            return builder.search_markdown(input_string);
+        }
+        if self.document {
+            builder.search_document(input_string)
+        } else {
+            builder.search(input_string)
+        }
+    }
+}
+
+/// Highlighted HTML, with the terms searched for, match counts and any parse errors.
+#[derive(Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct HighlightResult {
+    html: String,
+    /// The terms actually searched for, once the query has been cleaned up.
+    terms: Vec<String>,
+    statistics: MatchStatistics,
+    diagnostics: Diagnostics,
+}
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "Query")]
+    pub type QueryObject;
+
+    #[wasm_bindgen(typescript_type = "HighlightOptions")]
+    pub type HighlightOptionsObject;
+}
+
+/// Highlights `query` in `input_string`, configured by an optional `options` object. `query` is
+/// either a string of whitespace-separated terms or an array of terms. Throws if `options` has a
+/// key which is not a known setting, or a value of the wrong type.
+#[wasm_bindgen]
+pub fn highlight(
+    input_string: &str,
+    query: QueryObject,
+    options: Option<HighlightOptionsObject>,
+) -> Result<HighlightResult, JsError> {
+    let (query, options) = parse_arguments(query, options)?;
+    Ok(highlight_document(input_string, &query, &options))
+}
+
+/// Like `highlight`, but for plain text: `input_string` is never parsed as HTML, and `&`, `<` and
+/// `>` in it are escaped in the returned `html`. The `document`, `markdown` and `skipTags`
+/// options have no effect.
+#[wasm_bindgen]
+pub fn highlight_text(
+    input_string: &str,
+    query: QueryObject,
+    options: Option<HighlightOptionsObject>,
+) -> Result<HighlightResult, JsError> {
+    let (query, options) = parse_arguments(query, options)?;
+    let builder = options.builder(&query);
+    let document = builder.search_text(input_string);
+    Ok(HighlightResult {
+        html: document.serialize(&options.serialization),
+        terms: builder.effective_terms(),
+        statistics: document.statistics().clone(),
+        diagnostics: document.diagnostics(),
+    })
+}
+
+/// Matches found by `highlight_ranges`, with the terms searched for and match counts.
+#[derive(Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct HighlightRangesResult {
+    /// Where each match is: a path of child indices from the element the HTML was inserted into
+    /// (or the document) to a text node, with offsets into it.
+    ranges: Vec<HighlightRange>,
+    /// The terms actually searched for, once the query has been cleaned up.
+    terms: Vec<String>,
+    statistics: MatchStatistics,
+}
+
+/// Like `highlight`, but returns where matches are instead of inserting highlight elements, for
+/// highlighting with the CSS Custom Highlight API. Each range has a `path` of child indices
+/// (counting every node, as `childNodes` does) from the element `input_string` was set as the
+/// `innerHTML` of to a text node, and `start` and `end` offsets into that text node, each
+/// counted in UTF-16 code units (`utf16`), UTF-8 bytes (`bytes`) and Unicode scalar values
+/// (`chars`):
+///
+/// ```js
+/// const { ranges } = highlight_ranges(container.innerHTML, "apple");
+/// const highlight = new Highlight(
+///   ...ranges.map(({ path, start, end }) => {
+///     const node = path.reduce((parent, index) => parent.childNodes[index], container);
+///     const range = new Range();
+///     range.setStart(node, start.utf16);
+///     range.setEnd(node, end.utf16);
+///     return range;
+///   }),
+/// );
+/// CSS.highlights.set("search", highlight);
+/// ```
+///
+/// Unless `markdown` is set, each range also has a `source` span giving where the match is in
+/// `input_string` itself, as offsets and as 1-based line numbers with columns, for decorating a
+/// view of the HTML source. Text the parser moved elsewhere has no `source`.
+#[wasm_bindgen]
+pub fn highlight_ranges(
+    input_string: &str,
+    query: QueryObject,
+    options: Option<HighlightOptionsObject>,
+) -> Result<HighlightRangesResult, JsError> {
+    let (query, options) = parse_arguments(query, options)?;
+    let builder = options.builder(&query);
+    let HighlightRanges { ranges, statistics } = options.ranges(&builder, input_string);
+    Ok(HighlightRangesResult {
+        ranges,
+        terms: builder.effective_terms(),
+        statistics,
+    })
+}
+
+/// HTML parsed once, to be highlighted for any number of queries without parsing it again, as
+/// when highlighting an article as the user types:
+///
+/// ```js
+/// const article = new ParsedHtml(articleHtml);
+/// input.addEventListener("input", () => {
+///   container.innerHTML = article.highlight(input.value).html;
+/// });
+/// ```
+///
+/// Call `free()` when done with it, to release the parsed DOM.
+#[wasm_bindgen]
+pub struct ParsedHtml {
+    html: Html,
+    #[cfg(feature = "markdown")]
+    markdown: bool,
+}
+
+#[wasm_bindgen]
+impl ParsedHtml {
+    /// Parses `input_string`. Only the `document` and `markdown` options are used, and they apply
+    /// to every later `highlight`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        input_string: &str,
+        options: Option<HighlightOptionsObject>,
+    ) -> Result<ParsedHtml, JsError> {
+        let options = parse_options(options)?;
+        #[cfg(feature = "markdown")]
+        if options.markdown {
+            return Ok(ParsedHtml {
+                html: Html::parse_markdown(input_string),
+                markdown: true,
+            });
+        }
+        Ok(ParsedHtml {
+            html: parse(input_string, options.document),
+            #[cfg(feature = "markdown")]
+            markdown: false,
+        })
+    }
+
+    /// Highlights `query` in the parsed HTML, as `highlight` does. The parsed HTML is left
+    /// unchanged, ready for the next query. Throws if `options` sets `document` or `markdown`,
+    /// which are given to the constructor, or `preserveSource`, which is not supported.
+    pub fn highlight(
+        &self,
+        query: QueryObject,
+        options: Option<HighlightOptionsObject>,
+    ) -> Result<HighlightResult, JsError> {
+        let (query, options) = parse_arguments(query, options)?;
+        #[cfg(feature = "markdown")]
+        let markdown = options.markdown;
+        #[cfg(not(feature = "markdown"))]
+        let markdown = false;
+        if options.document || markdown {
+            return Err(JsError::new(
+                "the document and markdown options are given to the ParsedHtml constructor",
+            ));
+        }
+        if options.preserve_source {
+            return Err(JsError::new(
+                "the preserveSource option is not supported by ParsedHtml",
+            ));
+        }
+        let builder = options.builder(&query);
+        #[cfg(feature = "markdown")]
+        let document = if self.markdown {
+            builder.search_markdown_dom(self.html.clone())
+        } else {
+            builder.search_dom(self.html.clone())
+        };
+        #[cfg(not(feature = "markdown"))]
+        let document = builder.search_dom(self.html.clone());
+        Ok(HighlightResult {
+            html: document.serialize(&options.serialization),
+            terms: builder.effective_terms(),
+            statistics: document.statistics().clone(),
+            diagnostics: document.diagnostics(),
+        })
+    }
+}
+
+/// Highlights one document for a query typed a letter at a time. Like `ParsedHtml`, the HTML is
+/// parsed once; in addition, when each term of a query contains a term of the previous one (as
+/// when the user types another letter), only the text which matched last time is searched
+/// again, keeping each keystroke fast on long pages:
+///
+/// ```js
+/// const highlighter = new IncrementalHighlighter(articleHtml, { classes: ["hit"] });
+/// input.addEventListener("input", () => {
+///   container.innerHTML = highlighter.highlight(input.value).html;
+/// });
+/// ```
+///
+/// The options are fixed when it is created. Call `free()` when done with it.
+#[wasm_bindgen]
+pub struct IncrementalHighlighter {
+    search: IncrementalSearch,
+    options: HighlightOptions,
+}
+
+#[wasm_bindgen]
+impl IncrementalHighlighter {
+    /// Parses `input_string` to highlight with `options`, which are the same as for `highlight`.
+    /// Throws if `options` sets `preserveSource`, which is not supported.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        input_string: &str,
+        options: Option<HighlightOptionsObject>,
+    ) -> Result<IncrementalHighlighter, JsError> {
+        let options = parse_options(options)?;
+        if options.preserve_source {
+            return Err(JsError::new(
+                "the preserveSource option is not supported by IncrementalHighlighter",
+            ));
+        }
+        #[cfg(feature = "markdown")]
+        if options.markdown {
+            return Ok(IncrementalHighlighter {
+                search: IncrementalSearch::markdown(input_string),
+                options,
+            });
+        }
+        Ok(IncrementalHighlighter {
+            search: IncrementalSearch::new(parse(input_string, options.document)),
+            options,
+        })
+    }
+
+    /// Highlights `query`, a string of whitespace-separated terms or an array of terms.
+    pub fn highlight(&mut self, query: QueryObject) -> Result<HighlightResult, JsError> {
+        let query: Query = serde_wasm_bindgen::from_value(query.into())
+            .map_err(|error| JsError::new(&format!("invalid query: {error}")))?;
+        let builder = self.options.builder(&query);
+        let document = self.search.search(&builder);
+        Ok(HighlightResult {
+            html: document.serialize(&self.options.serialization),
+            terms: builder.effective_terms(),
+            statistics: document.statistics().clone(),
+            diagnostics: document.diagnostics(),
+        })
+    }
+}
+
+fn parse(input_string: &str, document: bool) -> Html {
+    if document {
+        Html::parse_document(input_string)
+    } else {
+        Html::parse(input_string)
+    }
+}
+
+fn parse_arguments(
+    query: QueryObject,
+    options: Option<HighlightOptionsObject>,
+) -> Result<(Query, HighlightOptions), JsError> {
+    let query: Query = serde_wasm_bindgen::from_value(query.into())
+        .map_err(|error| JsError::new(&format!("invalid query: {error}")))?;
+    Ok((query, parse_options(options)?))
+}
+
+fn parse_options(options: Option<HighlightOptionsObject>) -> Result<HighlightOptions, JsError> {
+    match options {
+        Some(options) => serde_wasm_bindgen::from_value(options.into())
+            .map_err(|error| JsError::new(&format!("invalid highlight options: {error}"))),
+        None => Ok(HighlightOptions::default()),
+    }
+}
+
+fn highlight_document(
+    input_string: &str,
+    query: &Query,
+    options: &HighlightOptions,
+) -> HighlightResult {
+    let builder = options.builder(query);
+    #[cfg(feature = "markdown")]
+    let preserve_source = options.preserve_source && !options.markdown;
+    #[cfg(not(feature = "markdown"))]
+    let preserve_source = options.preserve_source;
+    if preserve_source {
+        let spliced = if options.document {
+            builder.splice_document(input_string)
+        } else {
+            builder.splice(input_string)
+        };
+        return HighlightResult {
+            html: spliced.html,
+            terms: builder.effective_terms(),
+            statistics: spliced.statistics,
+            diagnostics: spliced.diagnostics,
+        };
+    }
+    let document = options.search(&builder, input_string);
+    HighlightResult {
+        html: document.serialize(&options.serialization),
+        terms: builder.effective_terms(),
+        statistics: document.statistics().clone(),
+        diagnostics: document.diagnostics(),
+    }
+}
+
+/// Like `highlight_search_terms`, but returns an object `{ html, statistics, diagnostics }`,
+/// where `diagnostics` holds the parse errors (`{ message, line }`) and quirks mode html5ever
+/// reported for the input.
+#[wasm_bindgen]
+pub fn highlight_search_terms_with_diagnostics(
+    input_string: &str,
+    search_pattern: &str,
+) -> HighlightResult {
+    highlight_document(
+        input_string,
+        &Query::Text(search_pattern.to_string()),
+        &HighlightOptions::default(),
+    )
+}
@@ -0,0 +1,245 @@
+//! Tests for the `searchlite` command-line tool.
+
+#![cfg(feature = "cli")]
+
+use serde_json::Value;
+use std::{
+    env, fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+/*
+ * An empty directory to run a test in, named after the test.
+ */
+fn scratch_directory(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("searchlite-cli-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn write_file(directory: &Path, path: &str, contents: &str) {
+    let path = directory.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+fn searchlite(directory: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_searchlite"))
+        .args(args)
+        .current_dir(directory)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // a run which rejects its arguments may exit before reading its input
+    let written = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    if let Err(error) = written {
+        assert_eq!(error.kind(), ErrorKind::BrokenPipe);
+    }
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn highlights_standard_input() {
+    let directory = scratch_directory("stdin");
+    let output = searchlite(&directory, &["--query", "apple"], "<p>An apple</p>");
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        r#"<p>An <mark id="search-match" data-searchlite="">apple</mark></p>"#
+    );
+}
+
+#[test]
+fn collects_inputs_from_directories_and_files() {
+    let directory = scratch_directory("inputs");
+    write_file(&directory, "site/b.html", "<p>apple</p>");
+    write_file(&directory, "site/nested/a.htm", "<p>apple pie</p>");
+    write_file(&directory, "site/notes.txt", "apple");
+    write_file(&directory, "extra.html", "<p>pear</p>");
+    let output = searchlite(
+        &directory,
+        &[
+            "--query",
+            "apple",
+            "--output",
+            "stats",
+            "site",
+            "extra.html",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    let statistics: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let paths: Vec<&str> = statistics
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|input| input["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths, ["site/b.html", "site/nested/a.htm", "extra.html"]);
+}
+
+#[test]
+fn out_dir_mirrors_input_paths() {
+    let directory = scratch_directory("out-dir");
+    write_file(&directory, "site/index.html", "<p>apple</p>");
+    write_file(&directory, "site/fruit/index.html", "<p>pear</p>");
+    write_file(&directory, "a/index.html", "<p>apple</p>");
+    write_file(&directory, "b/index.html", "<p>apple</p>");
+
+    let output = searchlite(
+        &directory,
+        &["--query", "apple", "--out-dir", "out", "site"],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(directory.join("out/index.html")).unwrap(),
+        r#"<p><mark id="search-match" data-searchlite="">apple</mark></p>"#
+    );
+    assert_eq!(
+        fs::read_to_string(directory.join("out/fruit/index.html")).unwrap(),
+        "<p>pear</p>"
+    );
+
+    let output = searchlite(
+        &directory,
+        &[
+            "--query",
+            "apple",
+            "--out-dir",
+            "files",
+            "a/index.html",
+            "./b/index.html",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert!(directory.join("files/a/index.html").is_file());
+    assert!(directory.join("files/b/index.html").is_file());
+}
+
+#[test]
+fn out_dir_refuses_to_overwrite_outputs() {
+    let directory = scratch_directory("collision");
+    write_file(&directory, "a/index.html", "<p>apple</p>");
+    write_file(&directory, "b/index.html", "<p>pear</p>");
+    let output = searchlite(
+        &directory,
+        &["--query", "apple", "--out-dir", "out", "a", "b"],
+        "",
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("index.html"));
+    assert!(!directory.join("out").exists());
+}
+
+#[test]
+fn stats_report_counts_as_json() {
+    let directory = scratch_directory("stats");
+    let output = searchlite(
+        &directory,
+        &["--query", "apple pie", "--output", "stats"],
+        "<p>Apple pie, apple tart</p>",
+    );
+    assert!(output.status.success());
+    let statistics: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(statistics["total"], 3);
+    assert_eq!(statistics["terms"][0]["term"], "apple");
+    assert_eq!(statistics["terms"][0]["count"], 2);
+    assert_eq!(statistics["terms"][1]["count"], 1);
+    assert_eq!(statistics["budgetExhausted"], false);
+    assert!(statistics.get("path").is_none());
+}
+
+#[test]
+fn exit_status_reports_errors_and_missing_matches() {
+    let directory = scratch_directory("exit");
+    write_file(&directory, "a.html", "<p>apple</p>");
+    write_file(&directory, "b.html", "<p>pear</p>");
+
+    let output = searchlite(&directory, &["--query", "apple", "a.html", "b.html"], "");
+    assert!(!output.status.success());
+
+    let output = searchlite(&directory, &["--query", "apple", "missing.html"], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing.html"));
+
+    let output = searchlite(
+        &directory,
+        &["--query", "plum", "--require-match", "a.html"],
+        "",
+    );
+    assert!(!output.status.success());
+    let output = searchlite(&directory, &["--query", "plum", "a.html"], "");
+    assert!(output.status.success());
+}
+
+#[test]
+fn empty_query_leaves_input_unchanged() {
+    let directory = scratch_directory("empty-query");
+    let output = searchlite(&directory, &["--query", ""], "<p>An apple</p>");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "<p>An apple</p>");
+
+    let output = searchlite(
+        &directory,
+        &["--query", "  ", "--require-match"],
+        "<p>An apple</p>",
+    );
+    assert!(!output.status.success());
+}
+
+#[test]
+fn rejects_options_which_would_be_ignored() {
+    let directory = scratch_directory("ignored-options");
+    write_file(&directory, "a.html", "<p>apple</p>");
+    for args in [
+        &[
+            "--query",
+            "apple",
+            "--output",
+            "stats",
+            "--out-dir",
+            "out",
+            "a.html",
+        ][..],
+        &[
+            "--query",
+            "apple",
+            "--output",
+            "snippets",
+            "--out-dir",
+            "out",
+            "a.html",
+        ],
+        &[
+            "--query",
+            "apple",
+            "--preserve-source",
+            "--match-attribute",
+            "alt",
+        ],
+        &[
+            "--query",
+            "apple",
+            "--preserve-source",
+            "--attribute-match-class",
+            "hit",
+        ],
+    ] {
+        let output = searchlite(&directory, args, "<p>apple</p>");
+        assert!(!output.status.success(), "{args:?}");
+        assert!(stdout(&output).is_empty(), "{args:?}");
+    }
+    assert!(!directory.join("out").exists());
+}
@@ -3,11 +3,99 @@
 #![cfg(target_arch = "wasm32")]
 
 extern crate wasm_bindgen_test;
+use searchlite::wasm::{
+    highlight_ranges, HighlightOptionsObject, IncrementalHighlighter, ParsedHtml, QueryObject,
+};
+use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_test::*;
 
 wasm_bindgen_test_configure!(run_in_browser);
 
+fn query(terms: &str) -> QueryObject {
+    JsValue::from_str(terms).unchecked_into()
+}
+
+fn options(json: &str) -> Option<HighlightOptionsObject> {
+    Some(js_sys::JSON::parse(json).unwrap().unchecked_into())
+}
+
+/*
+ * The property at `path` (such as `["statistics", "total"]`) of `result` serialised to a
+ * JavaScript object.
+ */
+fn property<T: serde::Serialize>(result: &T, path: &[&str]) -> JsValue {
+    let value = serde_wasm_bindgen::to_value(result).unwrap();
+    path.iter().fold(value, |value, key| {
+        js_sys::Reflect::get(&value, &JsValue::from_str(key)).unwrap()
+    })
+}
+
+#[wasm_bindgen_test]
+fn parsed_html_highlights_each_query() {
+    let parsed = ParsedHtml::new("<p>Apples and pears</p>", None)
+        .ok()
+        .unwrap();
+    let apple = parsed.highlight(query("apple"), None).ok().unwrap();
+    assert_eq!(
+        property(&apple, &["html"]).as_string().unwrap(),
+        r#"<p><mark id="search-match" data-searchlite="">Apple</mark>s and pears</p>"#
+    );
+    let pear = parsed
+        .highlight(query("pear"), options(r#"{ "classes": ["hit"] }"#))
+        .ok()
+        .unwrap();
+    assert_eq!(
+        property(&pear, &["html"]).as_string().unwrap(),
+        r#"<p>Apples and <mark id="search-match" class="hit" data-searchlite="">pear</mark>s</p>"#
+    );
+}
+
+#[wasm_bindgen_test]
+fn parsed_html_rejects_parse_options() {
+    let parsed = ParsedHtml::new("<p>Apples</p>", None).ok().unwrap();
+    for rejected in [r#"{ "document": true }"#, r#"{ "preserveSource": true }"#] {
+        assert!(parsed.highlight(query("apple"), options(rejected)).is_err());
+    }
+}
+
+#[wasm_bindgen_test]
+fn incremental_highlighter_matches_each_keystroke() {
+    let mut highlighter = IncrementalHighlighter::new("<p>Apples and apricots</p>", None)
+        .ok()
+        .unwrap();
+    let counts: Vec<f64> = ["a", "ap", "app", "ap"]
+        .into_iter()
+        .map(|terms| {
+            let result = highlighter.highlight(query(terms)).ok().unwrap();
+            property(&result, &["statistics", "total"])
+                .as_f64()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(counts, [3.0, 2.0, 1.0, 2.0]);
+    assert!(
+        IncrementalHighlighter::new("<p>Apples</p>", options(r#"{ "preserveSource": true }"#))
+            .is_err()
+    );
+}
+
 #[wasm_bindgen_test]
-fn pass() {
-    assert_eq!(1 + 1, 2);
+fn highlight_ranges_give_paths_and_offsets() {
+    let result = highlight_ranges("<p>An <b>apple</b></p>", query("apple"), None)
+        .ok()
+        .unwrap();
+    let range = property(&result, &["ranges", "0"]);
+    let path: Vec<usize> = serde_wasm_bindgen::from_value(
+        js_sys::Reflect::get(&range, &JsValue::from_str("path")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(path, [0, 1, 0]);
+    let utf16 = |key: &str| {
+        let offset = js_sys::Reflect::get(&range, &JsValue::from_str(key)).unwrap();
+        js_sys::Reflect::get(&offset, &JsValue::from_str("utf16"))
+            .unwrap()
+            .as_f64()
+            .unwrap()
+    };
+    assert_eq!((utf16("start"), utf16("end")), (0.0, 5.0));
 }